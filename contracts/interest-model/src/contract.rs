@@ -0,0 +1,305 @@
+use cosmwasm_std::{
+    log, to_binary, Api, Env, Extern, HandleResponse, HumanAddr, InitResponse, InitResult,
+    Querier, QueryResult, StdResult, Storage, Uint128,
+};
+
+use secret_toolkit::utils::{pad_handle_result, pad_query_result};
+
+use lending_common::admin::{self, check_admin};
+use lending_common::exponential::Exp;
+use lending_common::interest::{get_utilization_rate, supply_rate_from_borrow_rate, JumpRate};
+
+use crate::msg::{HandleMsg, InitMsg, ModelResponse, QueryMsg, RateResponse};
+use crate::state::{get_config, set_config, Config};
+
+/// pad handle responses and log attributes to blocks of 256 bytes to prevent leaking info based on
+/// response size
+pub const BLOCK_SIZE: usize = 256;
+
+pub fn init<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    msg: InitMsg,
+) -> InitResult {
+    let config = Config {
+        admin: msg.admin.unwrap_or(env.message.sender),
+        pending_admin: None,
+        base_rate: msg.base_rate.u128(),
+        multiplier: msg.multiplier.u128(),
+        jump_multiplier: msg.jump_multiplier.u128(),
+        kink: msg.kink.u128(),
+    };
+    jump_rate(&config).validate()?;
+    set_config(&mut deps.storage, &config)?;
+
+    Ok(InitResponse::default())
+}
+
+pub fn handle<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    msg: HandleMsg,
+) -> StdResult<HandleResponse> {
+    let response = match msg {
+        HandleMsg::UpdateModel { base_rate, multiplier, jump_multiplier, kink } => {
+            try_update_model(deps, env, base_rate, multiplier, jump_multiplier, kink)
+        },
+        HandleMsg::SetPendingAdmin { address } => try_set_pending_admin(deps, env, address),
+        HandleMsg::AcceptAdmin {} => try_accept_admin(deps, env),
+    };
+    pad_handle_result(response, BLOCK_SIZE)
+}
+
+pub fn query<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>, msg: QueryMsg) -> QueryResult {
+    let response = match msg {
+        QueryMsg::BorrowRate { cash, borrows, reserves } => {
+            let config = get_config(&deps.storage)?;
+            let util = get_utilization_rate(cash.u128(), borrows.u128(), reserves.u128())?;
+            let rate = jump_rate(&config).borrow_rate(util)?;
+            to_binary(&RateResponse { rate: Uint128::from(rate) })
+        },
+        QueryMsg::SupplyRate { cash, borrows, reserves, reserve_factor } => {
+            let config = get_config(&deps.storage)?;
+            let (cash, borrows, reserves) = (cash.u128(), borrows.u128(), reserves.u128());
+            let borrow_rate = jump_rate(&config).borrow_rate(get_utilization_rate(cash, borrows, reserves)?)?;
+            let rate = supply_rate_from_borrow_rate(borrow_rate, cash, borrows, reserves, Exp::from(reserve_factor))?;
+            to_binary(&RateResponse { rate: Uint128::from(rate) })
+        },
+        QueryMsg::Model {} => try_query_model(deps),
+    };
+    pad_query_result(response, BLOCK_SIZE)
+}
+
+fn try_update_model<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    base_rate: Option<Uint128>,
+    multiplier: Option<Uint128>,
+    jump_multiplier: Option<Uint128>,
+    kink: Option<Uint128>,
+) -> StdResult<HandleResponse> {
+    let mut config = get_config(&deps.storage)?;
+    check_admin(&config.admin, &env)?;
+
+    if let Some(base_rate) = base_rate {
+        config.base_rate = base_rate.u128();
+    }
+    if let Some(multiplier) = multiplier {
+        config.multiplier = multiplier.u128();
+    }
+    if let Some(jump_multiplier) = jump_multiplier {
+        config.jump_multiplier = jump_multiplier.u128();
+    }
+    if let Some(kink) = kink {
+        config.kink = kink.u128();
+    }
+    jump_rate(&config).validate()?;
+    set_config(&mut deps.storage, &config)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![
+            log("action", "update_model"),
+            log("base_rate", config.base_rate),
+            log("multiplier", config.multiplier),
+            log("jump_multiplier", config.jump_multiplier),
+            log("kink", config.kink),
+        ],
+        data: None,
+    })
+}
+
+fn try_set_pending_admin<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    address: HumanAddr,
+) -> StdResult<HandleResponse> {
+    let mut config = get_config(&deps.storage)?;
+    let log = admin::set_pending_admin(&deps.api, &env, &config.admin, &mut config.pending_admin, address)?;
+    set_config(&mut deps.storage, &config)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log,
+        data: None,
+    })
+}
+
+fn try_accept_admin<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+) -> StdResult<HandleResponse> {
+    let mut config = get_config(&deps.storage)?;
+    let log = admin::accept_admin(&env, &mut config.admin, &mut config.pending_admin)?;
+    set_config(&mut deps.storage, &config)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log,
+        data: None,
+    })
+}
+
+fn try_query_model<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>) -> QueryResult {
+    let config = get_config(&deps.storage)?;
+    to_binary(&ModelResponse {
+        admin: config.admin,
+        pending_admin: config.pending_admin,
+        base_rate: Uint128::from(config.base_rate),
+        multiplier: Uint128::from(config.multiplier),
+        jump_multiplier: Uint128::from(config.jump_multiplier),
+        kink: Uint128::from(config.kink),
+    })
+}
+
+/// Returns the curve described by the config
+fn jump_rate(config: &Config) -> JumpRate {
+    JumpRate {
+        base_rate: Exp::from_mantissa(config.base_rate),
+        multiplier: Exp::from_mantissa(config.multiplier),
+        jump_multiplier: Exp::from_mantissa(config.jump_multiplier),
+        kink: Exp::from_mantissa(config.kink),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmwasm_std::from_binary;
+    use lending_common::exponential::EXP_SCALE;
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, MockApi, MockQuerier, MockStorage};
+    use lending_testing::{assert_generic_err, assert_unauthorized};
+
+    type MockDeps = Extern<MockStorage, MockApi, MockQuerier>;
+
+    fn init_msg(admin: Option<HumanAddr>) -> InitMsg {
+        InitMsg {
+            admin,
+            base_rate: Uint128::from(EXP_SCALE / 100),
+            multiplier: Uint128::from(EXP_SCALE / 10),
+            jump_multiplier: Uint128::from(EXP_SCALE),
+            kink: Uint128::from(EXP_SCALE * 8 / 10),
+        }
+    }
+
+    fn init_model() -> MockDeps {
+        let mut deps = mock_dependencies(20, &[]);
+        init(&mut deps, mock_env("creator", &[]), init_msg(None)).unwrap();
+        deps
+    }
+
+    fn query_model(deps: &MockDeps) -> ModelResponse {
+        from_binary(&query(deps, QueryMsg::Model {}).unwrap()).unwrap()
+    }
+
+    fn query_rate(deps: &MockDeps, msg: QueryMsg) -> u128 {
+        let response: RateResponse = from_binary(&query(deps, msg).unwrap()).unwrap();
+        response.rate.u128()
+    }
+
+    fn update_kink(deps: &mut MockDeps, sender: &str, kink: u128) -> StdResult<HandleResponse> {
+        let msg = HandleMsg::UpdateModel {
+            base_rate: None,
+            multiplier: None,
+            jump_multiplier: None,
+            kink: Some(Uint128::from(kink)),
+        };
+        handle(deps, mock_env(sender, &[]), msg)
+    }
+
+    #[test]
+    fn init_stores_the_curve() {
+        let deps = init_model();
+        let model = query_model(&deps);
+        assert_eq!(model.admin, HumanAddr::from("creator"));
+        assert_eq!(model.pending_admin, None);
+        assert_eq!(model.base_rate, Uint128::from(EXP_SCALE / 100));
+        assert_eq!(model.multiplier, Uint128::from(EXP_SCALE / 10));
+        assert_eq!(model.jump_multiplier, Uint128::from(EXP_SCALE));
+        assert_eq!(model.kink, Uint128::from(EXP_SCALE * 8 / 10));
+
+        let mut deps = mock_dependencies(20, &[]);
+        init(&mut deps, mock_env("creator", &[]), init_msg(Some(HumanAddr::from("governance")))).unwrap();
+        assert_eq!(query_model(&deps).admin, HumanAddr::from("governance"));
+    }
+
+    #[test]
+    fn init_rejects_a_nonsensical_curve() {
+        let mut msg = init_msg(None);
+        msg.kink = Uint128::from(EXP_SCALE + 1);
+        assert_generic_err(
+            init(&mut mock_dependencies(20, &[]), mock_env("creator", &[]), msg),
+            "kink must not exceed 1",
+        );
+
+        let mut msg = init_msg(None);
+        msg.jump_multiplier = Uint128::from(EXP_SCALE / 100);
+        assert_generic_err(
+            init(&mut mock_dependencies(20, &[]), mock_env("creator", &[]), msg),
+            "jump multiplier must not be below the multiplier",
+        );
+    }
+
+    #[test]
+    fn rates_follow_the_curve() {
+        let deps = init_model();
+        // utilization 0.5: 0.5 * 0.1 + 0.01
+        let borrow_rate = query_rate(&deps, QueryMsg::BorrowRate {
+            cash: Uint128::from(100u128),
+            borrows: Uint128::from(100u128),
+            reserves: Uint128::from(0u128),
+        });
+        assert_eq!(borrow_rate, EXP_SCALE * 6 / 100);
+        // 0.5 * 0.06 * (1 - 0.1)
+        let supply_rate = query_rate(&deps, QueryMsg::SupplyRate {
+            cash: Uint128::from(100u128),
+            borrows: Uint128::from(100u128),
+            reserves: Uint128::from(0u128),
+            reserve_factor: Uint128::from(EXP_SCALE / 10),
+        });
+        assert_eq!(supply_rate, EXP_SCALE * 27 / 1000);
+        // utilization 1: 0.8 * 0.1 + 0.01 + 0.2 * 1
+        let borrow_rate = query_rate(&deps, QueryMsg::BorrowRate {
+            cash: Uint128::from(0u128),
+            borrows: Uint128::from(100u128),
+            reserves: Uint128::from(0u128),
+        });
+        assert_eq!(borrow_rate, EXP_SCALE * 29 / 100);
+    }
+
+    #[test]
+    fn only_the_admin_updates_the_model() {
+        let mut deps = init_model();
+        assert_unauthorized(update_kink(&mut deps, "intruder", EXP_SCALE / 2));
+        assert_generic_err(update_kink(&mut deps, "creator", EXP_SCALE + 1), "kink must not exceed 1");
+        assert_eq!(query_model(&deps).kink, Uint128::from(EXP_SCALE * 8 / 10));
+
+        update_kink(&mut deps, "creator", EXP_SCALE / 2).unwrap();
+        let model = query_model(&deps);
+        assert_eq!(model.kink, Uint128::from(EXP_SCALE / 2));
+        assert_eq!(model.multiplier, Uint128::from(EXP_SCALE / 10));
+    }
+
+    #[test]
+    fn admin_is_handed_over_in_two_steps() {
+        let mut deps = init_model();
+        let propose = HandleMsg::SetPendingAdmin { address: HumanAddr::from("governance") };
+        assert_unauthorized(handle(&mut deps, mock_env("intruder", &[]), propose.clone()));
+        handle(&mut deps, mock_env("creator", &[]), propose).unwrap();
+        assert_eq!(query_model(&deps).pending_admin, Some(HumanAddr::from("governance")));
+
+        assert_generic_err(
+            handle(&mut deps, mock_env("intruder", &[]), HandleMsg::AcceptAdmin {}),
+            "The sender is not the pending admin",
+        );
+        // The admin keeps its role until the handover is accepted
+        update_kink(&mut deps, "creator", EXP_SCALE / 2).unwrap();
+
+        handle(&mut deps, mock_env("governance", &[]), HandleMsg::AcceptAdmin {}).unwrap();
+        let model = query_model(&deps);
+        assert_eq!(model.admin, HumanAddr::from("governance"));
+        assert_eq!(model.pending_admin, None);
+        assert_unauthorized(update_kink(&mut deps, "creator", EXP_SCALE / 4));
+        update_kink(&mut deps, "governance", EXP_SCALE / 4).unwrap();
+    }
+}
@@ -0,0 +1,68 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use cosmwasm_std::{HumanAddr, Uint128};
+
+/// Jump rate curve shared by every market pointing at this contract.
+/// All values are fixed-point decimals scaled by 10^18, rates are per period of the markets.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct InitMsg {
+    /// governance address of the model, defaults to the instantiator
+    pub admin: Option<HumanAddr>,
+    pub base_rate: Uint128,
+    pub multiplier: Uint128,
+    pub jump_multiplier: Uint128,
+    pub kink: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum HandleMsg {
+    /// admin only: replace the curve, unset fields are left unchanged
+    UpdateModel {
+        base_rate: Option<Uint128>,
+        multiplier: Option<Uint128>,
+        jump_multiplier: Option<Uint128>,
+        kink: Option<Uint128>,
+    },
+    /// propose a new admin, who becomes admin once they accept
+    SetPendingAdmin {
+        address: HumanAddr,
+    },
+    /// accept the admin role proposed to the sender
+    AcceptAdmin {},
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryMsg {
+    BorrowRate {
+        cash: Uint128,
+        borrows: Uint128,
+        reserves: Uint128,
+    },
+    SupplyRate {
+        cash: Uint128,
+        borrows: Uint128,
+        reserves: Uint128,
+        reserve_factor: Uint128,
+    },
+    Model {},
+}
+
+/// `BorrowRate` and `SupplyRate` query response
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RateResponse {
+    pub rate: Uint128,
+}
+
+/// `Model` query response
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ModelResponse {
+    pub admin: HumanAddr,
+    pub pending_admin: Option<HumanAddr>,
+    pub base_rate: Uint128,
+    pub multiplier: Uint128,
+    pub jump_multiplier: Uint128,
+    pub kink: Uint128,
+}
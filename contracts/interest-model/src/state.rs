@@ -0,0 +1,29 @@
+use serde::{Deserialize, Serialize};
+
+use cosmwasm_std::{HumanAddr, ReadonlyStorage, StdResult, Storage};
+
+use lending_common::storage::{load, save};
+
+/// storage key for contract config
+pub const CONFIG_KEY: &[u8] = b"config";
+
+/// Config struct, the curve values are mantissas of fixed-point decimals scaled by 10^18
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Config {
+    pub admin: HumanAddr,
+    pub pending_admin: Option<HumanAddr>,
+    pub base_rate: u128,
+    pub multiplier: u128,
+    pub jump_multiplier: u128,
+    pub kink: u128,
+}
+
+/// Get config
+pub fn get_config<S: ReadonlyStorage>(storage: &S) -> StdResult<Config> {
+    load(storage, CONFIG_KEY)
+}
+
+/// Set config
+pub fn set_config<S: Storage>(storage: &mut S, config: &Config) -> StdResult<()> {
+    save(storage, CONFIG_KEY, config)
+}
@@ -0,0 +1,55 @@
+// Two-step handover of the admin role of a contract: the admin proposes a pending admin, who
+// only takes over once it accepts, so the role can never be handed to a wrong address.
+use std::mem;
+
+use cosmwasm_std::{log, Api, Env, HumanAddr, LogAttribute, StdError, StdResult};
+
+/// Returns an error if the sender is not `admin`
+pub fn check_admin(admin: &HumanAddr, env: &Env) -> StdResult<()> {
+    if env.message.sender != *admin {
+        return Err(StdError::unauthorized());
+    }
+    Ok(())
+}
+
+/// Proposes `address` as the next admin, returns the log of the proposal.
+/// Only the current `admin` may propose.
+pub fn set_pending_admin<A: Api>(
+    api: &A,
+    env: &Env,
+    admin: &HumanAddr,
+    pending_admin: &mut Option<HumanAddr>,
+    address: HumanAddr,
+) -> StdResult<Vec<LogAttribute>> {
+    check_admin(admin, env)?;
+
+    // Make sure the address is valid before proposing it
+    api.canonical_address(&address)?;
+
+    let log = vec![
+        log("action", "set_pending_admin"),
+        log("admin", admin.as_str()),
+        log("pending_admin", address.as_str()),
+    ];
+    *pending_admin = Some(address);
+    Ok(log)
+}
+
+/// Makes the sender the admin if it is the pending admin, returns the log of the handover
+pub fn accept_admin(
+    env: &Env,
+    admin: &mut HumanAddr,
+    pending_admin: &mut Option<HumanAddr>,
+) -> StdResult<Vec<LogAttribute>> {
+    if pending_admin.as_ref() != Some(&env.message.sender) {
+        return Err(StdError::generic_err("The sender is not the pending admin"));
+    }
+
+    let old_admin = mem::replace(admin, env.message.sender.clone());
+    *pending_admin = None;
+    Ok(vec![
+        log("action", "accept_admin"),
+        log("old_admin", old_admin.as_str()),
+        log("new_admin", admin.as_str()),
+    ])
+}
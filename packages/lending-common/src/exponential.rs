@@ -0,0 +1,285 @@
+//! exponential math lib
+//! Fixed-point decimals with 18 digits of precision. Every rate, factor and index of the market
+//! is stored as the mantissa of an `Exp`, i.e. the value multiplied by 10^18.
+use std::fmt;
+
+use cosmwasm_std::{StdError, StdResult, Uint128};
+use uint::construct_uint;
+
+construct_uint! {
+    /// 256-bit unsigned integer holding intermediate products so they can never overflow
+    pub struct U256(4);
+}
+
+/// 10^18, the mantissa of 1
+pub const EXP_SCALE: u128 = 1_000_000_000_000_000_000;
+
+/// how to round the result of an operation that loses precision
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Rounding {
+    /// towards zero
+    Down,
+    /// away from zero
+    Up,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Exp {
+    mantissa: u128,
+}
+
+impl Exp {
+    pub const fn zero() -> Self {
+        Exp { mantissa: 0 }
+    }
+
+    pub const fn one() -> Self {
+        Exp { mantissa: EXP_SCALE }
+    }
+
+    /// Returns the Exp whose mantissa, i.e. value * 10^18, is `mantissa`
+    pub const fn from_mantissa(mantissa: u128) -> Self {
+        Exp { mantissa }
+    }
+
+    /// Returns `num / denom`
+    pub fn from_ratio(num: u128, denom: u128, rounding: Rounding) -> StdResult<Self> {
+        Ok(Exp {
+            mantissa: mul_div(num, EXP_SCALE, denom, rounding)?,
+        })
+    }
+
+    /// Returns the integer `value` as an Exp
+    pub fn from_integer(value: u128) -> StdResult<Self> {
+        Exp::from_ratio(value, 1, Rounding::Down)
+    }
+
+    pub fn mantissa(&self) -> u128 {
+        self.mantissa
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.mantissa == 0
+    }
+
+    pub fn checked_add(self, other: Exp) -> StdResult<Exp> {
+        Ok(Exp {
+            mantissa: self.mantissa.checked_add(other.mantissa).ok_or_else(|| overflow("add"))?,
+        })
+    }
+
+    pub fn checked_sub(self, other: Exp) -> StdResult<Exp> {
+        Ok(Exp {
+            mantissa: self.mantissa.checked_sub(other.mantissa).ok_or_else(|| underflow("sub"))?,
+        })
+    }
+
+    /// Returns `self * other`
+    pub fn checked_mul(self, other: Exp, rounding: Rounding) -> StdResult<Exp> {
+        Ok(Exp {
+            mantissa: mul_div(self.mantissa, other.mantissa, EXP_SCALE, rounding)?,
+        })
+    }
+
+    /// Returns `self / other`
+    pub fn checked_div(self, other: Exp, rounding: Rounding) -> StdResult<Exp> {
+        Ok(Exp {
+            mantissa: mul_div(self.mantissa, EXP_SCALE, other.mantissa, rounding)?,
+        })
+    }
+
+    /// Returns `self * scalar`
+    pub fn mul_scalar(self, scalar: u128) -> StdResult<Exp> {
+        Ok(Exp {
+            mantissa: self.mantissa.checked_mul(scalar).ok_or_else(|| overflow("mul_scalar"))?,
+        })
+    }
+
+    /// Returns the integer part of `self * scalar`, rounded as requested
+    pub fn mul_scalar_to_int(self, scalar: u128, rounding: Rounding) -> StdResult<u128> {
+        mul_div(self.mantissa, scalar, EXP_SCALE, rounding)
+    }
+
+    /// Returns the integer part of `scalar / self`, rounded as requested
+    pub fn div_scalar_to_int(self, scalar: u128, rounding: Rounding) -> StdResult<u128> {
+        mul_div(scalar, EXP_SCALE, self.mantissa, rounding)
+    }
+
+    /// Returns `self ^ exponent`, by repeated squaring
+    pub fn checked_pow(self, exponent: u32) -> StdResult<Exp> {
+        let mut result = Exp::one();
+        let mut base = self;
+        let mut exponent = exponent;
+        while exponent > 0 {
+            if exponent & 1 == 1 {
+                result = result.checked_mul(base, Rounding::Down)?;
+            }
+            exponent >>= 1;
+            if exponent > 0 {
+                base = base.checked_mul(base, Rounding::Down)?;
+            }
+        }
+        Ok(result)
+    }
+
+    /// Returns the value of the Exp as an integer, rounded as requested.
+    /// Use `mantissa` or `Uint128::from` to keep the 18 decimals.
+    pub fn to_integer(self, rounding: Rounding) -> u128 {
+        let quotient = self.mantissa / EXP_SCALE;
+        match rounding {
+            Rounding::Up if self.mantissa % EXP_SCALE != 0 => quotient + 1,
+            _ => quotient,
+        }
+    }
+}
+
+impl fmt::Display for Exp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let whole = self.mantissa / EXP_SCALE;
+        let fractional = self.mantissa % EXP_SCALE;
+        if fractional == 0 {
+            write!(f, "{}", whole)
+        } else {
+            let digits = format!("{:018}", fractional);
+            write!(f, "{}.{}", whole, digits.trim_end_matches('0'))
+        }
+    }
+}
+
+/// Reads a mantissa, as rates and factors are sent in messages
+impl From<Uint128> for Exp {
+    fn from(mantissa: Uint128) -> Self {
+        Exp::from_mantissa(mantissa.u128())
+    }
+}
+
+/// Returns the mantissa, not the integer part, see `Exp::to_integer` for that
+impl From<Exp> for Uint128 {
+    fn from(exp: Exp) -> Self {
+        Uint128::from(exp.mantissa)
+    }
+}
+
+/// Returns `a * b / denom` rounded as requested. The product is computed on 256 bits so only
+/// a result that does not fit in 128 bits is an overflow.
+pub fn mul_div(a: u128, b: u128, denom: u128, rounding: Rounding) -> StdResult<u128> {
+    if denom == 0 {
+        return Err(StdError::generic_err("Math error: division by zero"));
+    }
+    let product = U256::from(a) * U256::from(b);
+    let denom = U256::from(denom);
+    let mut quotient = product / denom;
+    if rounding == Rounding::Up && !(product % denom).is_zero() {
+        quotient = quotient + U256::one();
+    }
+    if quotient > U256::from(u128::MAX) {
+        return Err(StdError::generic_err("Math error: multiplication overflow"));
+    }
+    Ok(quotient.low_u128())
+}
+
+/// Returns `a + b` or an error on overflow
+pub fn add_uint(a: u128, b: u128) -> StdResult<u128> {
+    a.checked_add(b)
+        .ok_or_else(|| StdError::generic_err("Math error: addition overflow"))
+}
+
+/// Returns `a - b` or an error on underflow
+pub fn sub_uint(a: u128, b: u128) -> StdResult<u128> {
+    a.checked_sub(b)
+        .ok_or_else(|| StdError::generic_err("Math error: subtraction underflow"))
+}
+
+fn overflow(operation: &str) -> StdError {
+    StdError::generic_err(format!("Exp {} overflow", operation))
+}
+
+fn underflow(operation: &str) -> StdError {
+    StdError::generic_err(format!("Exp {} underflow", operation))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lending_testing::assert_generic_err;
+
+    #[test]
+    fn mul_div_rounds_as_requested() {
+        assert_eq!(mul_div(10, 1, 3, Rounding::Down).unwrap(), 3);
+        assert_eq!(mul_div(10, 1, 3, Rounding::Up).unwrap(), 4);
+        // An exact quotient is never rounded up
+        assert_eq!(mul_div(9, 1, 3, Rounding::Up).unwrap(), 3);
+        assert_eq!(mul_div(0, 5, 3, Rounding::Up).unwrap(), 0);
+    }
+
+    #[test]
+    fn mul_div_only_overflows_on_the_result() {
+        // The product does not fit in 128 bits but the quotient does
+        assert_eq!(mul_div(u128::MAX, 4, 4, Rounding::Down).unwrap(), u128::MAX);
+        assert_generic_err(mul_div(u128::MAX, 2, 1, Rounding::Down), "Math error: multiplication overflow");
+        assert_generic_err(mul_div(u128::MAX, 3, 2, Rounding::Up), "Math error: multiplication overflow");
+    }
+
+    #[test]
+    fn division_by_zero_is_an_error() {
+        assert_generic_err(mul_div(1, 1, 0, Rounding::Down), "Math error: division by zero");
+        assert_generic_err(Exp::from_ratio(1, 0, Rounding::Down), "Math error: division by zero");
+        assert_generic_err(Exp::one().checked_div(Exp::zero(), Rounding::Down), "Math error: division by zero");
+        assert_generic_err(Exp::zero().div_scalar_to_int(1, Rounding::Down), "Math error: division by zero");
+    }
+
+    #[test]
+    fn overflows_and_underflows_are_errors() {
+        let max = Exp::from_mantissa(u128::MAX);
+        assert_generic_err(max.checked_add(Exp::from_mantissa(1)), "Exp add overflow");
+        assert_generic_err(Exp::zero().checked_sub(Exp::from_mantissa(1)), "Exp sub underflow");
+        assert_generic_err(max.mul_scalar(2), "Exp mul_scalar overflow");
+        assert_generic_err(max.checked_mul(Exp::from_integer(2).unwrap(), Rounding::Down), "Math error: multiplication overflow");
+        assert_generic_err(Exp::from_integer(u128::MAX), "Math error: multiplication overflow");
+        assert_generic_err(add_uint(u128::MAX, 1), "Math error: addition overflow");
+        assert_generic_err(sub_uint(0, 1), "Math error: subtraction underflow");
+    }
+
+    #[test]
+    fn from_ratio_rounds_the_last_digit() {
+        assert_eq!(Exp::from_ratio(1, 3, Rounding::Down).unwrap().mantissa(), 333_333_333_333_333_333);
+        assert_eq!(Exp::from_ratio(1, 3, Rounding::Up).unwrap().mantissa(), 333_333_333_333_333_334);
+        assert_eq!(Exp::from_ratio(3, 2, Rounding::Up).unwrap(), Exp::from_mantissa(EXP_SCALE * 3 / 2));
+        assert_eq!(Exp::from_ratio(7, 7, Rounding::Down).unwrap(), Exp::one());
+        assert_eq!(Exp::from_integer(5).unwrap().mantissa(), 5 * EXP_SCALE);
+    }
+
+    #[test]
+    fn checked_pow_multiplies_by_squaring() {
+        let one_point_one = Exp::from_mantissa(EXP_SCALE * 11 / 10);
+        assert_eq!(one_point_one.checked_pow(0).unwrap(), Exp::one());
+        assert_eq!(one_point_one.checked_pow(1).unwrap(), one_point_one);
+        assert_eq!(one_point_one.checked_pow(2).unwrap(), Exp::from_mantissa(EXP_SCALE * 121 / 100));
+        assert_eq!(Exp::from_integer(2).unwrap().checked_pow(10).unwrap(), Exp::from_integer(1_024).unwrap());
+        // Each multiplication rounds down
+        let third = Exp::from_ratio(1, 3, Rounding::Down).unwrap();
+        assert_eq!(third.checked_pow(2).unwrap().mantissa(), 111_111_111_111_111_110);
+        assert_generic_err(Exp::from_integer(10).unwrap().checked_pow(30), "Math error: multiplication overflow");
+    }
+
+    #[test]
+    fn display_trims_the_decimals() {
+        assert_eq!(Exp::zero().to_string(), "0");
+        assert_eq!(Exp::from_integer(42).unwrap().to_string(), "42");
+        assert_eq!(Exp::from_mantissa(EXP_SCALE * 3 / 2).to_string(), "1.5");
+        assert_eq!(Exp::from_mantissa(1).to_string(), "0.000000000000000001");
+        assert_eq!(Exp::from_mantissa(12_340_000_000_000_000).to_string(), "0.01234");
+    }
+
+    #[test]
+    fn conversions_keep_the_mantissa_or_the_integer_part() {
+        let exp = Exp::from_mantissa(EXP_SCALE * 5 / 2);
+        assert_eq!(Uint128::from(exp), Uint128::from(EXP_SCALE * 5 / 2));
+        assert_eq!(Exp::from(Uint128::from(EXP_SCALE * 5 / 2)), exp);
+        assert_eq!(exp.to_integer(Rounding::Down), 2);
+        assert_eq!(exp.to_integer(Rounding::Up), 3);
+        assert_eq!(Exp::from_integer(2).unwrap().to_integer(Rounding::Up), 2);
+        assert_eq!(exp.mul_scalar_to_int(3, Rounding::Down).unwrap(), 7);
+        assert_eq!(exp.mul_scalar_to_int(3, Rounding::Up).unwrap(), 8);
+    }
+}
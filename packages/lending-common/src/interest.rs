@@ -0,0 +1,74 @@
+// Interest rate curves shared by the markets and the interest model contract, so both compute
+// exactly the same rates from the same balances.
+use cosmwasm_std::{StdError, StdResult};
+
+use crate::exponential::{Exp, Rounding};
+
+/// Borrow rate per period rising linearly with utilization up to the kink, then with the steeper
+/// jump multiplier
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct JumpRate {
+    pub base_rate: Exp,
+    pub multiplier: Exp,
+    pub jump_multiplier: Exp,
+    pub kink: Exp,
+}
+
+impl JumpRate {
+    /// Returns an error if the parameters do not describe a sensible curve
+    pub fn validate(&self) -> StdResult<()> {
+        if self.kink > Exp::one() {
+            return Err(StdError::generic_err(format!(
+                "kink must not exceed 1: kink: {}",
+                self.kink.mantissa())
+            )
+            );
+        }
+        if self.jump_multiplier < self.multiplier {
+            return Err(StdError::generic_err(format!(
+                "jump multiplier must not be below the multiplier: multiplier: {}, jump_multiplier: {}",
+                self.multiplier.mantissa(), self.jump_multiplier.mantissa())
+            )
+            );
+        }
+        Ok(())
+    }
+
+    /// Returns the borrow rate per period at `utilization`
+    pub fn borrow_rate(&self, utilization: Exp) -> StdResult<Exp> {
+        if utilization <= self.kink {
+            utilization.checked_mul(self.multiplier, Rounding::Down)?.checked_add(self.base_rate)
+        } else {
+            let normal_rate = self.kink.checked_mul(self.multiplier, Rounding::Down)?.checked_add(self.base_rate)?;
+            let excess_util = utilization.checked_sub(self.kink)?;
+            excess_util
+                .checked_mul(self.jump_multiplier, Rounding::Down)?
+                .checked_add(normal_rate)
+        }
+    }
+}
+
+/// Returns borrows / (cash + borrows - reserves)
+pub fn get_utilization_rate(cash: u128, borrows: u128, reserves: u128) -> StdResult<Exp> {
+    if borrows == 0 {
+        return Ok(Exp::zero());
+    }
+    let total = cash
+        .checked_add(borrows)
+        .and_then(|total| total.checked_sub(reserves))
+        .ok_or_else(|| StdError::generic_err("Invalid market balances for utilization rate"))?;
+    Exp::from_ratio(borrows, total, Rounding::Down)
+}
+
+/// Returns utilization * borrow_rate * (1 - reserve_factor)
+pub fn supply_rate_from_borrow_rate(
+    borrow_rate: Exp,
+    cash: u128,
+    borrows: u128,
+    reserves: u128,
+    reserve_factor: Exp,
+) -> StdResult<Exp> {
+    let one_minus_reserve_factor = Exp::one().checked_sub(reserve_factor)?;
+    let rate_to_pool = borrow_rate.checked_mul(one_minus_reserve_factor, Rounding::Down)?;
+    get_utilization_rate(cash, borrows, reserves)?.checked_mul(rate_to_pool, Rounding::Down)
+}
@@ -0,0 +1,5 @@
+// Code shared by the market and the interest model contract
+pub mod admin;
+pub mod exponential;
+pub mod interest;
+pub mod storage;
@@ -0,0 +1,26 @@
+// Assertions shared by the unit tests of the market and the interest model contract.
+// Only ever a dev-dependency, so none of it ships with the contracts.
+use std::fmt::Debug;
+
+use cosmwasm_std::{StdError, StdResult};
+
+/// Panics unless `result` is a generic error whose message contains `expected`
+pub fn assert_generic_err<T: Debug>(result: StdResult<T>, expected: &str) {
+    match result {
+        Err(StdError::GenericErr { msg, .. }) => assert!(
+            msg.contains(expected),
+            "expected an error containing {:?}, got {:?}",
+            expected,
+            msg
+        ),
+        other => panic!("expected an error containing {:?}, got {:?}", expected, other),
+    }
+}
+
+/// Panics unless `result` is an unauthorized error
+pub fn assert_unauthorized<T: Debug>(result: StdResult<T>) {
+    match result {
+        Err(StdError::Unauthorized { .. }) => {},
+        other => panic!("expected an unauthorized error, got {:?}", other),
+    }
+}
@@ -1,27 +1,276 @@
 use cosmwasm_std::{
-    log, Api, Env, Extern, HandleResponse,  Querier,
-    StdError, StdResult, Storage, Uint128, BankMsg, CosmosMsg, Coin
+    from_binary, log, to_binary, Api, Binary, CanonicalAddr, Env, Extern, HandleResponse,  Querier,
+    StdError, StdResult, Storage, Uint128, BankMsg, CosmosMsg, Coin, HumanAddr
 };
 
-use std::convert::TryInto;
+use secret_toolkit::snip20::transfer_msg;
 
-use crate::state::{get_state, set_state, get_config, set_config, set_borrow_balance, get_borrow_balance, BorrowSnapshot};
+use lending_common::exponential::{add_uint, mul_div, sub_uint, Exp, Rounding};
 
-use crate::interest_model::{get_borrow_rate};
-use crate::exponential::truncate;
+use crate::contract::BLOCK_SIZE;
+use crate::msg::{HandleAnswer, ReceiveMsg, ResponseStatus};
+use crate::state::{get_state, set_state, get_config, set_config, set_borrow_balance, get_borrow_balance, get_balance, BorrowSnapshot, Config, State};
+
+use crate::controller::{
+    borrow_allowed, liquidate_borrow_allowed, mint_allowed, redeem_allowed, repay_allowed, seize_allowed,
+};
+use crate::interest_model::{compound_interest_factor, get_borrow_rate, AccrualMode};
+use crate::history::{
+    store_burn, store_lending_event, store_mint, store_transfer, StoredLendingAction,
+    StoredLendingEvent,
+};
 use crate::token::{mint_tokens, burn_tokens};
 
+pub fn try_receive<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    from: HumanAddr,
+    amount: Uint128,
+    msg: Option<Binary>,
+) -> StdResult<HandleResponse> {
+    // Only the underlying token contract can pay into the market
+    let config = get_config(&deps.storage)?;
+    match &config.underlying_token {
+        Some(token) if token.address == env.message.sender => {},
+        _ => {
+            return Err(StdError::generic_err(format!(
+                "Received tokens from an unexpected contract: {}",
+                env.message.sender)
+            )
+            );
+        }
+    }
+    if amount.u128() == 0 {
+        return Err(StdError::generic_err("Received amount must be greater than zero"));
+    }
+
+    let msg: ReceiveMsg = match msg {
+        Some(msg) => from_binary(&msg)?,
+        None => return Err(StdError::generic_err("Received tokens without a message")),
+    };
+    match msg {
+        ReceiveMsg::Mint {} => mint(deps, env, from, amount.u128()),
+        ReceiveMsg::RepayBorrow {} => repay_borrow(deps, env, from, amount.u128()),
+        ReceiveMsg::LiquidateBorrow { borrower, repay_amount } => {
+            liquidate_borrow(deps, env, from, borrower, repay_amount, amount.u128())
+        },
+    }
+}
+
 pub fn try_repay_borrow<S: Storage, A: Api, Q: Querier>(
-    _deps: &mut Extern<S, A, Q>,
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+) -> StdResult<HandleResponse> {
+    // Check native currency transfer in the market denom
+    let config = get_config(&deps.storage)?;
+    let repay_amount_in = get_sent_amount(&env, &config)?;
+
+    let payer = env.message.sender.clone();
+    repay_borrow(deps, env, payer, repay_amount_in)
+}
+
+/// Repays the borrow of `payer` with `repay_amount_in` of the underlying that was paid in
+pub fn repay_borrow<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
     env: Env,
+    payer: HumanAddr,
+    repay_amount_in: u128,
 ) -> StdResult<HandleResponse> {
+
+    accrue_interest(deps, env.clone())?;
+
+    let current_block = env.block.height;
+    let state = get_state(&deps.storage)?;
+    if current_block != state.block_number {
+        return Err(StdError::generic_err(format!(
+            "Market is not fresh: current_block: {}, market_block: {}",
+            current_block, state.block_number)
+        )
+        );
+    }
+
+    repay_allowed(deps, env.clone(), &payer, &payer, repay_amount_in)?;
+
+    let borrower_raw = deps.api.canonical_address(&payer)?;
+    let (repaid, remaining) = repay_borrow_fresh(deps, &borrower_raw, repay_amount_in)?;
+    record_lending_event(deps, &env, &borrower_raw, StoredLendingAction::Repay, repaid, 0)?;
+
+    // Refund whatever exceeds the outstanding borrow balance
+    let config = get_config(&deps.storage)?;
+    let mut messages = vec![];
+    let refund = sub_uint(repay_amount_in, repaid)?;
+    if refund > 0 {
+        messages.push(transfer_underlying(&env, &config, &payer, refund)?);
+    }
+
     let res = HandleResponse {
-        messages: vec![],
-        log: vec![
-            log("action", "repay_borrow"),
-            log("sender", env.message.sender.as_str()),
-        ],
-        data: None,
+        messages,
+        log: vec![log("action", "repay_borrow")],
+        data: Some(to_binary(&HandleAnswer::Repay {
+            status: ResponseStatus::Success,
+            repaid: Uint128::from(repaid),
+            remaining: Uint128::from(remaining),
+        })?),
+    };
+    Ok(res)
+}
+
+/// Applies a repayment of up to `repay_amount` against the borrow balance of `borrower`.
+/// The market must have accrued interest in the current block.
+/// Returns the amount actually repaid and the borrower's remaining borrow balance.
+fn repay_borrow_fresh<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    borrower: &CanonicalAddr,
+    repay_amount: u128,
+) -> StdResult<(u128, u128)> {
+    let account_borrow = get_account_borrow(deps, borrower)?;
+    if account_borrow == 0 {
+        return Err(StdError::generic_err("The account has no outstanding borrow to repay"));
+    }
+
+    // Never take more than what is owed, the rest is refunded by the caller
+    let repaid = if repay_amount > account_borrow { account_borrow } else { repay_amount };
+    let new_account_borrow = sub_uint(account_borrow, repaid)?;
+
+    // Set new cash and total borrows for contract
+    let mut new_state = get_state(&deps.storage)?;
+    new_state.cash = add_uint(new_state.cash, repaid)?;
+    // Per-account balances round up on accrual, so their sum can exceed total borrows
+    new_state.total_borrows = new_state.total_borrows.saturating_sub(repaid);
+    set_state(&mut deps.storage, &new_state)?;
+
+    // Set new borrow balance for the borrower
+    let new_borrow_balance = BorrowSnapshot {
+        principal: new_account_borrow,
+        interest_index: new_state.borrow_index
+    };
+    set_borrow_balance(&mut deps.storage, borrower, Some(new_borrow_balance))?;
+
+    Ok((repaid, new_account_borrow))
+}
+
+pub fn try_liquidate_borrow<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    borrower: HumanAddr,
+    repay_amount: Uint128,
+) -> StdResult<HandleResponse> {
+    // Check native currency transfer in the market denom
+    let config = get_config(&deps.storage)?;
+    let repay_amount_in = get_sent_amount(&env, &config)?;
+
+    let liquidator = env.message.sender.clone();
+    liquidate_borrow(deps, env, liquidator, borrower, repay_amount, repay_amount_in)
+}
+
+/// Repays `repay_amount` of the borrow of `borrower` on behalf of `liquidator` out of the
+/// `repay_amount_in` of the underlying that was paid in, and seizes cTokens of the borrower
+pub fn liquidate_borrow<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    liquidator: HumanAddr,
+    borrower: HumanAddr,
+    repay_amount: Uint128,
+    repay_amount_in: u128,
+) -> StdResult<HandleResponse> {
+
+    accrue_interest(deps, env.clone())?;
+
+    let current_block = env.block.height;
+    let state = get_state(&deps.storage)?;
+    if current_block != state.block_number {
+        return Err(StdError::generic_err(format!(
+            "Market is not fresh: current_block: {}, market_block: {}",
+            current_block, state.block_number)
+        )
+        );
+    }
+
+    if borrower == liquidator {
+        return Err(StdError::generic_err("A borrower cannot liquidate their own borrow"));
+    }
+    if repay_amount.u128() == 0 {
+        return Err(StdError::generic_err("Liquidation repay amount must be greater than zero"));
+    }
+
+    let config = get_config(&deps.storage)?;
+    if repay_amount_in < repay_amount.u128() {
+        return Err(StdError::generic_err(format!(
+            "Insufficient funds sent to liquidate: sent: {}, repay_amount: {}",
+            repay_amount_in, repay_amount)
+        )
+        );
+    }
+
+    liquidate_borrow_allowed(deps, env.clone(), &liquidator, &borrower, repay_amount.u128())?;
+
+    let borrower_raw = deps.api.canonical_address(&borrower)?;
+
+    // The liquidator may close at most close_factor of the borrow at once
+    let account_borrow = get_account_borrow(deps, &borrower_raw)?;
+    let max_close = Exp::from_mantissa(config.close_factor).mul_scalar_to_int(account_borrow, Rounding::Down)?;
+    if repay_amount.u128() > max_close {
+        return Err(StdError::generic_err(format!(
+            "Liquidation repay amount exceeds the close factor: repay_amount: {}, max_close: {}",
+            repay_amount, max_close)
+        )
+        );
+    }
+
+    // Calculate the cTokens to seize, including the liquidation incentive
+    let exchange_rate = get_exchange_rate(deps, env.clone())?;
+    let seize_value = Exp::from_mantissa(config.liquidation_incentive).mul_scalar_to_int(repay_amount.u128(), Rounding::Down)?;
+    let seize_tokens = exchange_rate.div_scalar_to_int(seize_value, Rounding::Down)?;
+    let borrower_balance = get_balance(&deps.storage, &borrower_raw)?;
+    if seize_tokens > borrower_balance {
+        return Err(StdError::generic_err(format!(
+            "The borrower has insufficient collateral to seize: seize_tokens: {}, balance: {}",
+            seize_tokens, borrower_balance)
+        )
+        );
+    }
+
+    seize_allowed(deps, env.clone(), &liquidator, &borrower, seize_tokens)?;
+
+    let (repaid, remaining) = repay_borrow_fresh(deps, &borrower_raw, repay_amount.u128())?;
+
+    // Move the seized cTokens from the borrower to the liquidator
+    let liquidator_raw = deps.api.canonical_address(&liquidator)?;
+    burn_tokens(&mut deps.storage, &borrower_raw, seize_tokens)?;
+    mint_tokens(&mut deps.storage, &liquidator_raw, seize_tokens)?;
+    store_transfer(
+        &mut deps.storage,
+        &borrower_raw,
+        &liquidator_raw,
+        &liquidator_raw,
+        seize_tokens,
+        &env.block,
+    )?;
+
+    // Both sides of the liquidation see it in their loan ledger
+    let liquidation = StoredLendingAction::Liquidation {
+        liquidator: liquidator_raw.clone(),
+        borrower: borrower_raw.clone(),
+    };
+    record_lending_event(deps, &env, &borrower_raw, liquidation.clone(), repaid, seize_tokens)?;
+    record_lending_event(deps, &env, &liquidator_raw, liquidation, repaid, seize_tokens)?;
+
+    // Refund whatever exceeds the repaid amount
+    let mut messages = vec![];
+    let refund = sub_uint(repay_amount_in, repaid)?;
+    if refund > 0 {
+        messages.push(transfer_underlying(&env, &config, &liquidator, refund)?);
+    }
+
+    let res = HandleResponse {
+        messages,
+        log: vec![log("action", "liquidate_borrow")],
+        data: Some(to_binary(&HandleAnswer::LiquidateBorrow {
+            status: ResponseStatus::Success,
+            repaid: Uint128::from(repaid),
+            seized_tokens: Uint128::from(seize_tokens),
+            remaining: Uint128::from(remaining),
+        })?),
     };
     Ok(res)
 }
@@ -44,7 +293,8 @@ pub fn try_borrow<S: Storage, A: Api, Q: Querier>(
         );
     }
 
-    // TODO: get query from controller contract whether the sender is allowed to borrow
+    // Check if the sender has enough collateral to take the borrow
+    borrow_allowed(deps, env.clone(), &env.message.sender, borrow_amount.u128())?;
 
     // Check if the pool has enough balance to lend to the sender
     if state.cash < borrow_amount.u128() {
@@ -55,17 +305,16 @@ pub fn try_borrow<S: Storage, A: Api, Q: Querier>(
         );
     }
 
-    // Get borrow balance of the sender
-    let sender_raw = deps.api.canonical_address(&env.message.sender)?;
     // get borrow balance
-    let account_borrow = get_account_borrow(deps, env.clone())?;
-    let new_account_borrow = account_borrow + borrow_amount.u128();
+    let sender_raw = deps.api.canonical_address(&env.message.sender)?;
+    let account_borrow = get_account_borrow(deps, &sender_raw)?;
+    let new_account_borrow = add_uint(account_borrow, borrow_amount.u128())?;
 
 
     // Set new cash amount for contract
     let mut new_state = get_state(&deps.storage)?;
-    new_state.cash = new_state.cash - borrow_amount.u128();
-    new_state.total_borrows += borrow_amount.u128();
+    new_state.cash = sub_uint(new_state.cash, borrow_amount.u128())?;
+    new_state.total_borrows = add_uint(new_state.total_borrows, borrow_amount.u128())?;
     set_state(&mut deps.storage, &new_state)?;
 
     // Set new borrow balance for the sender
@@ -74,27 +323,19 @@ pub fn try_borrow<S: Storage, A: Api, Q: Querier>(
         interest_index: new_state.borrow_index
     };
     set_borrow_balance(&mut deps.storage, &sender_raw, Some(new_borrow_balance))?;
+    record_lending_event(deps, &env, &sender_raw, StoredLendingAction::Borrow, borrow_amount.u128(), 0)?;
 
     // Transfer native token to the user
-    // TODO: in this case it is hard coded to luna, include denom in contract config
-    let native_transfer: CosmosMsg = CosmosMsg::Bank(BankMsg::Send {
-        from_address: env.contract.address.clone(),
-        to_address: env.message.sender.clone(),
-        amount: vec![Coin {
-            denom: "uluna".to_string(),
-            amount: borrow_amount.clone(),
-        }],
-    });
+    let config = get_config(&deps.storage)?;
+    let borrow_transfer = transfer_underlying(&env, &config, &env.message.sender, borrow_amount.u128())?;
 
     let res = HandleResponse {
-        messages: vec![native_transfer],
-        log: vec![
-            log("action", "borrow"),
-            log("sender", env.message.sender.as_str()),
-            log("new_account_borrow", new_account_borrow.clone()),
-            log("new_total_borrows", new_state.clone().total_borrows)
-        ],
-        data: None,
+        messages: vec![borrow_transfer],
+        log: vec![log("action", "borrow")],
+        data: Some(to_binary(&HandleAnswer::Borrow {
+            status: ResponseStatus::Success,
+            account_borrow: Uint128::from(new_account_borrow),
+        })?),
     };
     Ok(res)
 }
@@ -103,6 +344,21 @@ pub fn try_mint<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
 ) -> StdResult<HandleResponse> {
+    // Check native currency transfer in the market denom
+    let config = get_config(&deps.storage)?;
+    let mint_amount = get_sent_amount(&env, &config)?;
+
+    let minter = env.message.sender.clone();
+    mint(deps, env, minter, mint_amount)
+}
+
+/// Mints cTokens to `minter` for the `mint_amount` of the underlying that was paid in
+pub fn mint<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    minter: HumanAddr,
+    mint_amount: u128,
+) -> StdResult<HandleResponse> {
 
     accrue_interest(deps, env.clone())?;
 
@@ -116,44 +372,55 @@ pub fn try_mint<S: Storage, A: Api, Q: Querier>(
         );
     }
 
-
-    // TODO: get query from controller contract whether the sender is allowed to borrow
-
-    // Check native currency transfer
-    let mint_amount = env.message.sent_funds[0].amount.u128();
+    mint_allowed(deps, env.clone(), &minter, mint_amount)?;
 
     // Get exchange rate derived from borrow and reserve
     let exchange_rate = get_exchange_rate(deps, env.clone())?;
 
-    let token_mint_amount = truncate(mint_amount * exchange_rate);
+    let token_mint_amount = exchange_rate.div_scalar_to_int(mint_amount, Rounding::Down)?;
 
     // Set new config
     let mut new_config = get_config(&deps.storage)?;
-    new_config.total_supply += token_mint_amount;
+    new_config.total_supply = add_uint(new_config.total_supply, token_mint_amount)?;
 
     // Set new cash amount for contract
     let mut new_state = get_state(&deps.storage)?;
-    new_state.cash += mint_amount;
+    new_state.cash = add_uint(new_state.cash, mint_amount)?;
     set_state(&mut deps.storage, &new_state)?;
 
     set_config(&mut deps.storage, &new_config)?;
 
-    // Mint token to the sender
-    let recipient_address_raw = deps.api.canonical_address(&env.message.sender)?;
+    // Mint token to the minter
+    let recipient_address_raw = deps.api.canonical_address(&minter)?;
     mint_tokens(
         &mut deps.storage,
         &recipient_address_raw,
         token_mint_amount,
     )?;
+    store_mint(
+        &mut deps.storage,
+        &recipient_address_raw,
+        &recipient_address_raw,
+        token_mint_amount,
+        &env.block,
+    )?;
+    record_lending_event(
+        deps,
+        &env,
+        &recipient_address_raw,
+        StoredLendingAction::Mint,
+        mint_amount,
+        token_mint_amount,
+    )?;
 
     let res = HandleResponse {
         messages: vec![],
-        log: vec![
-            log("action", "mint"),
-            log("sender", env.message.sender.as_str()),
-            log("minted_amount", token_mint_amount.clone())
-        ],
-        data: None,
+        log: vec![log("action", "mint")],
+        data: Some(to_binary(&HandleAnswer::Mint {
+            status: ResponseStatus::Success,
+            minted_tokens: Uint128::from(token_mint_amount),
+            exchange_rate: Uint128::from(exchange_rate),
+        })?),
     };
     Ok(res)
 }
@@ -161,7 +428,7 @@ pub fn try_mint<S: Storage, A: Api, Q: Querier>(
 pub fn try_redeem<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
-    redeem_tokens_in: Uint128
+    tokens: Uint128
 ) -> StdResult<HandleResponse> {
     accrue_interest(deps, env.clone())?;
 
@@ -175,55 +442,62 @@ pub fn try_redeem<S: Storage, A: Api, Q: Querier>(
         );
     }
 
-    // TODO: get query from controller contract whether the sender is allowed to borrow
-
     // Get exchange rate derived from borrow and reserve
     let exchange_rate = get_exchange_rate(deps, env.clone())?;
 
-    let redeem_native_in = env.message.sent_funds[0].amount.u128();
-    if redeem_tokens_in.u128() != 0 && redeem_native_in != 0 {
+    // Calculate the underlying paid out for the cTokens burnt
+    let redeem_tokens = tokens.u128();
+    let redeem_native = exchange_rate.mul_scalar_to_int(redeem_tokens, Rounding::Down)?;
+
+    redeem_fresh(deps, env, redeem_tokens, redeem_native)
+}
+
+pub fn try_redeem_underlying<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    amount: Uint128
+) -> StdResult<HandleResponse> {
+    accrue_interest(deps, env.clone())?;
+
+    let current_block = env.block.height;
+    let state = get_state(&deps.storage)?;
+    if current_block != state.block_number {
         return Err(StdError::generic_err(format!(
-            "one of redeeming tokens or asset must be 0: redeeming_cw20: {}, redeeming_native_currency: {}",
-            redeem_tokens_in.u128(), redeem_native_in)
+            "Market is not fresh: current_block: {}, market_block: {}",
+            current_block, state.block_number)
         )
         );
     }
-    // Calculate redeem amount
-    let (redeem_native, redeem_tokens, new_state) = match redeem_tokens_in.u128() {
-        x if x > 0 => {
-            // Set new cash amount for contract
-            let mut new_state = state.clone();
-            let redeem_native = truncate(exchange_rate * 100_000_000 * redeem_tokens_in.u128());
-            new_state.cash = new_state.cash - redeem_native;
-            (redeem_native, redeem_tokens_in.u128(), new_state)
-        },
-        _ => {
-            // Set new cash amount for contract
-            let mut new_state = state.clone();
-            new_state.cash +=redeem_native_in;
-            (redeem_native_in, truncate(redeem_native_in / (100_000_000 * exchange_rate)), new_state)
-        }
-    };
 
-    // Set new state for cash
-    set_state(&mut deps.storage, &new_state)?;
-
-    // Set new config
-    let mut new_config = get_config(&deps.storage)?;
-    new_config.total_supply = new_config.total_supply - redeem_tokens;
-    set_config(&mut deps.storage, &new_config)?;
+    // Get exchange rate derived from borrow and reserve
+    let exchange_rate = get_exchange_rate(deps, env.clone())?;
 
+    // Calculate the cTokens to burn for the underlying paid out,
+    // rounding up so the redeemer never gets more than their cTokens are worth
+    let redeem_native = amount.u128();
+    let redeem_tokens = exchange_rate.div_scalar_to_int(redeem_native, Rounding::Up)?;
 
+    redeem_fresh(deps, env, redeem_tokens, redeem_native)
+}
 
-    // Burn token to the sender
-    let recipient_address_raw = deps.api.canonical_address(&env.message.sender)?;
-    burn_tokens(
-        &mut deps.storage,
-        &recipient_address_raw,
-        redeem_tokens,
-    )?;
+/// Burns `redeem_tokens` cTokens of the sender and pays out `redeem_native` of the underlying.
+/// The market must have accrued interest in the current block.
+fn redeem_fresh<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    redeem_tokens: u128,
+    redeem_native: u128,
+) -> StdResult<HandleResponse> {
+    if redeem_tokens == 0 || redeem_native == 0 {
+        return Err(StdError::generic_err(format!(
+            "Redeem amount must be greater than zero: redeem_tokens: {}, redeem_native: {}",
+            redeem_tokens, redeem_native)
+        )
+        );
+    }
 
     // Check if the pool has enough balance
+    let state = get_state(&deps.storage)?;
     if state.cash < redeem_native {
         return Err(StdError::generic_err(format!(
             "The lending pool has insufficient cash: redeem_amount: {}, pool_reserve: {}",
@@ -232,110 +506,298 @@ pub fn try_redeem<S: Storage, A: Api, Q: Querier>(
         );
     }
 
-    // Transfer native token to the user
-    // TODO: in this case it is hard coded to luna, include denom in contract config
-    let native_transfer: CosmosMsg = CosmosMsg::Bank(BankMsg::Send {
-        from_address: env.contract.address.clone(),
-        to_address: env.message.sender.clone(),
-        amount: vec![Coin {
-            denom: "uluna".to_string(),
-            amount: Uint128::from(redeem_native.clone()),
-        }],
-    });
+    // Check if the sender holds the cTokens to burn
+    let redeemer_raw = deps.api.canonical_address(&env.message.sender)?;
+    let balance = get_balance(&deps.storage, &redeemer_raw)?;
+    if balance < redeem_tokens {
+        return Err(StdError::generic_err(format!(
+            "Insufficient cTokens to redeem: balance: {}, redeem_tokens: {}",
+            balance, redeem_tokens)
+        )
+        );
+    }
 
+    // Check if the sender keeps enough collateral for its borrow after redeeming
+    redeem_allowed(deps, env.clone(), &env.message.sender, redeem_tokens)?;
 
-    // TODO: write defense hook
+    // Set new cash amount for contract
+    let mut new_state = state;
+    new_state.cash = sub_uint(new_state.cash, redeem_native)?;
+    set_state(&mut deps.storage, &new_state)?;
+
+    // Set new config
+    let mut new_config = get_config(&deps.storage)?;
+    new_config.total_supply = sub_uint(new_config.total_supply, redeem_tokens)?;
+    set_config(&mut deps.storage, &new_config)?;
+
+    // Burn token of the sender
+    burn_tokens(
+        &mut deps.storage,
+        &redeemer_raw,
+        redeem_tokens,
+    )?;
+    store_burn(
+        &mut deps.storage,
+        &redeemer_raw,
+        &redeemer_raw,
+        redeem_tokens,
+        &env.block,
+    )?;
+    record_lending_event(
+        deps,
+        &env,
+        &redeemer_raw,
+        StoredLendingAction::Redeem,
+        redeem_native,
+        redeem_tokens,
+    )?;
+
+    // Transfer the underlying to the user
+    let redeem_transfer = transfer_underlying(&env, &new_config, &env.message.sender, redeem_native)?;
 
     let res = HandleResponse {
         messages: vec![
-            native_transfer
+            redeem_transfer
         ],
-        log: vec![
-            log("action", "redeem"),
-            log("sender", env.message.sender.as_str()),
-            log("redeem_tokens", redeem_tokens.clone()),
-            log("redeem_native", redeem_native.clone())
-        ],
-        data: None,
+        log: vec![log("action", "redeem")],
+        data: Some(to_binary(&HandleAnswer::Redeem {
+            status: ResponseStatus::Success,
+            redeem_tokens: Uint128::from(redeem_tokens),
+            redeem_amount: Uint128::from(redeem_native),
+        })?),
     };
     Ok(res)
 }
 
-fn accrue_interest<S: Storage, A: Api, Q: Querier>(deps: &mut Extern<S, A, Q>, env: Env) -> StdResult<()>  {
-    let prior_state = get_state(&deps.storage)?;
-
-    let borrow_rate = get_borrow_rate(prior_state.cash, prior_state.total_borrows, prior_state.total_reserves);
+/// Records `action` in the lending history of `account`, along with the market rates and the
+/// balances of the account once the action is applied
+fn record_lending_event<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: &Env,
+    account: &CanonicalAddr,
+    action: StoredLendingAction,
+    underlying_amount: u128,
+    ctoken_amount: u128,
+) -> StdResult<()> {
+    let config = get_config(&deps.storage)?;
+    let state = get_state(&deps.storage)?;
+    let event = StoredLendingEvent {
+        action,
+        underlying_amount,
+        ctoken_amount,
+        exchange_rate: calculate_exchange_rate(&config, &state)?.mantissa(),
+        borrow_index: state.borrow_index,
+        ctoken_balance: get_balance(&deps.storage, account)?,
+        borrow_balance: get_account_borrow(deps, account)?,
+        block_height: env.block.height,
+        block_time: env.block.time,
+    };
+    store_lending_event(&mut deps.storage, account, event)
+}
 
-    if borrow_rate > prior_state.max_borrow_rate {
+/// Returns the amount of the market denom sent along with the message.
+/// Exactly one coin, of the market denom and with a non-zero amount, must be sent.
+fn get_sent_amount(env: &Env, config: &Config) -> StdResult<u128> {
+    if let Some(token) = &config.underlying_token {
         return Err(StdError::generic_err(format!(
-            "borrow rate is absurdly high: borrow_rate: {}, max_borrow_rate: {}",
-            borrow_rate, prior_state.max_borrow_rate)
-        )
-        );
+            "This market only accepts its underlying token {} through Send",
+            token.address)
+        ));
     }
 
-    let current_block = env.block.height;
+    let denom = config.denom.as_str();
+    match env.message.sent_funds.as_slice() {
+        [] => Err(StdError::generic_err(format!(
+            "No funds were sent: expected {}",
+            denom)
+        )),
+        [coin] if coin.denom != denom => Err(StdError::generic_err(format!(
+            "Wrong denom sent: expected {}, got {}",
+            denom, coin.denom)
+        )),
+        [coin] if coin.amount.u128() == 0 => Err(StdError::generic_err(format!(
+            "Sent amount of {} must be greater than zero",
+            denom)
+        )),
+        [coin] => Ok(coin.amount.u128()),
+        coins => Err(StdError::generic_err(format!(
+            "Only {} can be sent, but {} coins were sent",
+            denom, coins.len())
+        )),
+    }
+}
 
-    let block_delta: u128 = (prior_state.block_number - current_block).try_into().unwrap();
+/// Returns a message paying out `amount` of the underlying from the contract to `recipient`,
+/// either as a native coin of the market denom or as a SNIP-20 transfer
+fn transfer_underlying(env: &Env, config: &Config, recipient: &HumanAddr, amount: u128) -> StdResult<CosmosMsg> {
+    match &config.underlying_token {
+        Some(token) => transfer_msg(
+            recipient.clone(),
+            Uint128::from(amount),
+            None,
+            BLOCK_SIZE,
+            token.code_hash.clone(),
+            token.address.clone(),
+        ),
+        None => Ok(CosmosMsg::Bank(BankMsg::Send {
+            from_address: env.contract.address.clone(),
+            to_address: recipient.clone(),
+            amount: vec![Coin {
+                denom: config.denom.clone(),
+                amount: Uint128::from(amount),
+            }],
+        })),
+    }
+}
 
-    // Calculate the interest accumulated into borrows and reserves and the new index:
-    let simple_interest_factor = borrow_rate * block_delta;
+/// Accrues interest on the market up to the current block and saves the new state
+pub fn accrue_interest<S: Storage, A: Api, Q: Querier>(deps: &mut Extern<S, A, Q>, env: Env) -> StdResult<()>  {
+    save_accrued_state(deps, env, false)
+}
 
-    let accumulated_interest = truncate(simple_interest_factor * prior_state.total_borrows);
-    let new_total_borrows = accumulated_interest + prior_state.total_borrows;
-    let new_total_reserves = truncate(accumulated_interest * prior_state.reserve_factor) + prior_state.total_reserves;
-    let new_borrow_index = truncate(simple_interest_factor * prior_state.borrow_index) + prior_state.borrow_index;
+/// Accrues interest like `accrue_interest`, but at the max borrow rate when the model asks for
+/// more, so the admin can still fix the parameters of such a market
+pub fn accrue_interest_at_most_max_rate<S: Storage, A: Api, Q: Querier>(deps: &mut Extern<S, A, Q>, env: Env) -> StdResult<()>  {
+    save_accrued_state(deps, env, true)
+}
 
-    // Set new state
-    let mut new_state = get_state(&deps.storage)?;
-    new_state.block_number = env.block.height;
-    new_state.borrow_index = new_borrow_index;
-    new_state.total_borrows = new_total_borrows;
-    new_state.total_reserves = new_total_reserves;
+fn save_accrued_state<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    clamp_borrow_rate: bool,
+) -> StdResult<()> {
+    let prior_state = get_state(&deps.storage)?;
 
-    set_state(&mut deps.storage, &new_state)?;
+    // Interest was already accrued in this block
+    if prior_state.block_number == env.block.height {
+        return Ok(());
+    }
 
+    let config = get_config(&deps.storage)?;
+    let new_state = accrued_state(&deps.querier, &config, &prior_state, env.block.height, env.block.time, clamp_borrow_rate)?;
+    set_state(&mut deps.storage, &new_state)?;
 
     Ok(())
 }
 
-fn get_exchange_rate<S: Storage, A: Api, Q: Querier>(deps: &mut Extern<S, A, Q>, _env: Env) -> StdResult<u128> {
+/// Returns the market state after accruing interest from the last accrual up to `current_block`
+/// and `current_time`. Depending on the accrual mode of the market, interest accrues over the
+/// elapsed blocks or the elapsed seconds.
+///
+/// # Arguments
+///
+/// * `querier` - a reference to the querier used to look up an external interest model
+/// * `config` - a reference to the market config
+/// * `prior_state` - a reference to the state at the last accrual
+/// * `current_block` - height to accrue interest up to
+/// * `current_time` - block time, in seconds, to accrue interest up to
+pub fn calculate_accrued_state<Q: Querier>(
+    querier: &Q,
+    config: &Config,
+    prior_state: &State,
+    current_block: u64,
+    current_time: u64,
+) -> StdResult<State> {
+    accrued_state(querier, config, prior_state, current_block, current_time, false)
+}
+
+fn accrued_state<Q: Querier>(
+    querier: &Q,
+    config: &Config,
+    prior_state: &State,
+    current_block: u64,
+    current_time: u64,
+    clamp_borrow_rate: bool,
+) -> StdResult<State> {
+    let block_delta = current_block.checked_sub(prior_state.block_number).ok_or_else(|| {
+        StdError::generic_err(format!(
+            "Cannot accrue interest backwards: current_block: {}, market_block: {}",
+            current_block, prior_state.block_number)
+        )
+    })?;
+    let time_delta = current_time.checked_sub(prior_state.last_accrual_time).ok_or_else(|| {
+        StdError::generic_err(format!(
+            "Cannot accrue interest backwards: current_time: {}, market_time: {}",
+            current_time, prior_state.last_accrual_time)
+        )
+    })?;
+    let period_delta = u128::from(match config.accrual_mode {
+        AccrualMode::Block => block_delta,
+        AccrualMode::Time => time_delta,
+    });
+
+    let mut new_state = prior_state.clone();
+    new_state.block_number = current_block;
+    new_state.last_accrual_time = current_time;
+    if period_delta == 0 {
+        return Ok(new_state);
+    }
+
+    let mut borrow_rate = get_borrow_rate(querier, &config.interest_model, prior_state.cash, prior_state.total_borrows, prior_state.total_reserves)?;
+
+    if clamp_borrow_rate && borrow_rate > Exp::from_mantissa(prior_state.max_borrow_rate) {
+        borrow_rate = Exp::from_mantissa(prior_state.max_borrow_rate);
+    }
+    if borrow_rate > Exp::from_mantissa(prior_state.max_borrow_rate) {
+        return Err(StdError::generic_err(format!(
+            "borrow rate is absurdly high: borrow_rate: {}, max_borrow_rate: {}",
+            borrow_rate, Exp::from_mantissa(prior_state.max_borrow_rate))
+        )
+        );
+    }
+
+    // Calculate the interest accumulated into borrows and reserves and the new index:
+    let interest_factor = if config.compound_interest {
+        compound_interest_factor(borrow_rate, period_delta)?
+    } else {
+        borrow_rate.mul_scalar(period_delta)?
+    };
+
+    let accumulated_interest = interest_factor.mul_scalar_to_int(prior_state.total_borrows, Rounding::Down)?;
+    new_state.total_borrows = add_uint(accumulated_interest, prior_state.total_borrows)?;
+    new_state.total_reserves = add_uint(
+        Exp::from_mantissa(prior_state.reserve_factor).mul_scalar_to_int(accumulated_interest, Rounding::Down)?,
+        prior_state.total_reserves,
+    )?;
+    new_state.borrow_index = add_uint(
+        interest_factor.mul_scalar_to_int(prior_state.borrow_index, Rounding::Down)?,
+        prior_state.borrow_index,
+    )?;
+
+    Ok(new_state)
+}
+
+/// Returns the amount of underlying one cToken is worth
+pub fn get_exchange_rate<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>, _env: Env) -> StdResult<Exp> {
     let config = get_config(&deps.storage)?;
+    let state = get_state(&deps.storage)?;
+    calculate_exchange_rate(&config, &state)
+}
 
+/// Returns the amount of underlying one cToken is worth in the given market state
+pub fn calculate_exchange_rate(config: &Config, prior_state: &State) -> StdResult<Exp> {
     // if total supply is zero
     if config.total_supply == 0u128 {
-        return Ok(config.initial_exchange_rate);
+        return Ok(Exp::from_mantissa(config.initial_exchange_rate));
     }
-    // else calculate exchange rate
-    let prior_state = get_state(&deps.storage)?;
 
+    // else calculate exchange rate
     let total_cash = prior_state.cash;
 
-    let cash_plus_borrows_minus_reserves = total_cash + prior_state.total_borrows - prior_state.total_reserves;
-
-    let exchange_rate = cash_plus_borrows_minus_reserves / config.total_supply * 100_000_000;
-
+    let cash_plus_borrows_minus_reserves = sub_uint(
+        add_uint(total_cash, prior_state.total_borrows)?,
+        prior_state.total_reserves,
+    )?;
 
-    Ok(exchange_rate)
+    Exp::from_ratio(cash_plus_borrows_minus_reserves, config.total_supply, Rounding::Down)
 }
 
 
-fn get_account_borrow<S: Storage, A: Api, Q: Querier>(deps: &mut Extern<S, A, Q>, env: Env) -> StdResult<u128> {
-    let sender_raw = deps.api.canonical_address(&env.message.sender)?;
-    let snapshot = get_borrow_balance(&deps.storage, &sender_raw);
-
-    let borrow_snapshot = match snapshot {
-        Some(s) => {
-            s
-        },
-        None => {
-            let init_borrow_snapshot = BorrowSnapshot {
-                principal: 0u128,
-                interest_index: 0u128
-            };
-            set_borrow_balance(&mut deps.storage, &sender_raw, Some(init_borrow_snapshot.clone()))?;
-            init_borrow_snapshot
-        }
+/// Returns the borrow balance of `account` at the current borrow index
+pub fn get_account_borrow<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>, account: &CanonicalAddr) -> StdResult<u128> {
+    let borrow_snapshot = match get_borrow_balance(&deps.storage, account) {
+        Some(s) => s,
+        None => return Ok(0),
     };
 
     if borrow_snapshot.principal == 0u128 {
@@ -345,6 +807,320 @@ fn get_account_borrow<S: Storage, A: Api, Q: Querier>(deps: &mut Extern<S, A, Q>
     }
 
     let state = get_state(&deps.storage)?;
-    let principal_time_index = borrow_snapshot.principal * state.borrow_index;
-    return Ok(principal_time_index / borrow_snapshot.interest_index);
+    mul_div(borrow_snapshot.principal, state.borrow_index, borrow_snapshot.interest_index, Rounding::Down)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmwasm_std::testing::mock_env;
+    use lending_common::exponential::EXP_SCALE;
+
+    use crate::history::{get_lending_events, LendingAction};
+    use crate::msg::HandleMsg;
+    use crate::interest_model::InterestModel;
+    use crate::testing::{
+        assert_generic_err, borrow, canonical, init_market, mint, mock_controller, mock_init_msg, mock_market, redeem,
+        repay, run, run_at, MockDeps, MOCK_DENOM,
+    };
+
+    /// Returns the lending history of `address`, oldest first, as
+    /// (action, underlying_amount, ctoken_amount, ctoken_balance, borrow_balance)
+    fn ledger(deps: &MockDeps, address: &str) -> Vec<(LendingAction, u128, u128, u128, u128)> {
+        let account = canonical(deps, address);
+        let (events, total) = get_lending_events(&deps.api, &deps.storage, &account, 0, 10).unwrap();
+        assert_eq!(total as usize, events.len());
+        events
+            .into_iter()
+            .rev()
+            .map(|event| {
+                (
+                    event.action,
+                    event.underlying_amount.u128(),
+                    event.ctoken_amount.u128(),
+                    event.ctoken_balance.u128(),
+                    event.borrow_balance.u128(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn every_action_is_recorded_with_the_balances_it_leaves() {
+        let mut deps = mock_market(None);
+
+        mint(&mut deps, "alice", 1_000).unwrap();
+        borrow(&mut deps, "alice", 300).unwrap();
+        repay(&mut deps, "alice", 100).unwrap();
+        redeem(&mut deps, "alice", 200).unwrap();
+
+        assert_eq!(
+            ledger(&deps, "alice"),
+            vec![
+                (LendingAction::Mint {}, 1_000, 1_000, 1_000, 0),
+                (LendingAction::Borrow {}, 300, 0, 1_000, 300),
+                (LendingAction::Repay {}, 100, 0, 1_000, 200),
+                (LendingAction::Redeem {}, 200, 200, 800, 200),
+            ]
+        );
+
+        let alice = canonical(&deps, "alice");
+        let (events, _) = get_lending_events(&deps.api, &deps.storage, &alice, 0, 1).unwrap();
+        assert_eq!(events[0].exchange_rate, Uint128::from(EXP_SCALE));
+        assert_eq!(events[0].borrow_index, Uint128::from(EXP_SCALE));
+        assert_eq!(events[0].block_height, mock_env("alice", &[]).block.height);
+    }
+
+    #[test]
+    fn the_whole_balance_can_be_repaid_after_interest_rounds_away_from_total_borrows() {
+        let mut msg = mock_init_msg();
+        msg.interest_model = Some(InterestModel::Fixed { rate: msg.max_borrow_rate });
+        let mut deps = init_market(msg).unwrap();
+        mint(&mut deps, "alice", 1_000).unwrap();
+        borrow(&mut deps, "alice", 300).unwrap();
+
+        // Accruing every block, the interest on 300 rounds down to nothing while the index grows
+        let mut env = mock_env("alice", &[]);
+        for _ in 0..700 {
+            env.block.height += 1;
+            accrue_interest(&mut deps, env.clone()).unwrap();
+        }
+        let alice = canonical(&deps, "alice");
+        assert_eq!(get_state(&deps.storage).unwrap().total_borrows, 300);
+        assert_eq!(get_account_borrow(&deps, &alice).unwrap(), 301);
+
+        run_at(&mut deps, env, 301, HandleMsg::RepayBorrow {}).unwrap();
+        assert_eq!(get_account_borrow(&deps, &alice).unwrap(), 0);
+        assert_eq!(get_state(&deps.storage).unwrap().total_borrows, 0);
+    }
+
+    #[test]
+    fn a_liquidation_is_recorded_for_the_borrower_and_the_liquidator() {
+        // The controller lets alice borrow past the local collateral factor and lets bob liquidate her
+        let mut deps = mock_market(Some(mock_controller()));
+        mint(&mut deps, "alice", 1_000).unwrap();
+        borrow(&mut deps, "alice", 800).unwrap();
+
+        let msg = HandleMsg::LiquidateBorrow {
+            borrower: HumanAddr::from("alice"),
+            repay_amount: Uint128::from(100u128),
+        };
+        run(&mut deps, "bob", 100, msg).unwrap();
+
+        // 100 repaid with an incentive of 1.1 seizes 110 cTokens worth 1 each
+        let liquidation = LendingAction::Liquidation {
+            liquidator: HumanAddr::from("bob"),
+            borrower: HumanAddr::from("alice"),
+        };
+        assert_eq!(
+            ledger(&deps, "alice"),
+            vec![
+                (LendingAction::Mint {}, 1_000, 1_000, 1_000, 0),
+                (LendingAction::Borrow {}, 800, 0, 1_000, 800),
+                (liquidation.clone(), 100, 110, 890, 700),
+            ]
+        );
+        assert_eq!(ledger(&deps, "bob"), vec![(liquidation, 100, 110, 110, 0)]);
+    }
+
+    /// Market lending at a fixed `rate` per period, where alice borrows 400M against 1G supplied
+    fn borrowed_market(rate: u128, compound_interest: bool, accrual_mode: AccrualMode) -> MockDeps {
+        let mut msg = mock_init_msg();
+        msg.interest_model = Some(InterestModel::Fixed { rate: Uint128::from(rate) });
+        msg.max_borrow_rate = Uint128::from(rate);
+        msg.compound_interest = Some(compound_interest);
+        msg.accrual_mode = Some(accrual_mode);
+        let mut deps = init_market(msg).unwrap();
+        mint(&mut deps, "alice", 1_000_000_000).unwrap();
+        borrow(&mut deps, "alice", 400_000_000).unwrap();
+        deps
+    }
+
+    /// Has bob mint 1 `blocks` and `seconds` after the default mock block, which accrues interest
+    fn mint_later(deps: &mut MockDeps, blocks: u64, seconds: u64) {
+        let mut env = mock_env("bob", &[]);
+        env.block.height += blocks;
+        env.block.time += seconds;
+        run_at(deps, env, 1, HandleMsg::Mint {}).unwrap();
+    }
+
+    #[test]
+    fn simple_interest_accrues_over_the_elapsed_blocks() {
+        let mut deps = borrowed_market(1_000_000_000_000, false, AccrualMode::Block);
+
+        // 0.000001 per block over 1000 blocks
+        mint_later(&mut deps, 1_000, 0);
+        let state = get_state(&deps.storage).unwrap();
+        assert_eq!(state.block_number, mock_env("bob", &[]).block.height + 1_000);
+        assert_eq!(state.borrow_index, EXP_SCALE + EXP_SCALE / 1_000);
+        assert_eq!(state.total_borrows, 400_400_000);
+        assert_eq!(state.total_reserves, 40_000);
+        assert_eq!(get_account_borrow(&deps, &canonical(&deps, "alice")).unwrap(), 400_400_000);
+
+        // Nothing accrues twice in the same block
+        mint_later(&mut deps, 1_000, 0);
+        assert_eq!(get_state(&deps.storage).unwrap(), State { cash: state.cash + 1, ..state });
+    }
+
+    #[test]
+    fn compound_interest_follows_the_binomial_expansion() {
+        let rate = 1_000_000_000_000;
+        let mut deps = borrowed_market(rate, true, AccrualMode::Block);
+
+        // 1000 * r + C(1000, 2) * r^2 + C(1000, 3) * r^3 with r = 0.000001
+        mint_later(&mut deps, 1_000, 0);
+        let state = get_state(&deps.storage).unwrap();
+        assert_eq!(state.borrow_index, EXP_SCALE + 1_000_499_666_167_000);
+        assert_eq!(state.total_borrows, 400_000_000 + 400_199);
+        assert_eq!(state.total_reserves, 40_019);
+
+        // The terms left out of the expansion are worth less than 10^-13 over 1000 blocks
+        let exact = Exp::from_mantissa(EXP_SCALE + rate).checked_pow(1_000).unwrap().mantissa();
+        assert!(exact > state.borrow_index && exact - state.borrow_index < 100_000);
+    }
+
+    #[test]
+    fn nothing_accrues_without_elapsed_periods() {
+        let mut deps = borrowed_market(1_000_000_000_000, true, AccrualMode::Block);
+        let config = get_config(&deps.storage).unwrap();
+        let state = get_state(&deps.storage).unwrap();
+        let env = mock_env("bob", &[]);
+
+        let accrued = calculate_accrued_state(&deps.querier, &config, &state, env.block.height, env.block.time).unwrap();
+        assert_eq!(accrued, state);
+
+        // Time passing in the same block does not accrue interest per block
+        mint_later(&mut deps, 0, 60);
+        let accrued = get_state(&deps.storage).unwrap();
+        assert_eq!(accrued, State { cash: state.cash + 1, ..state });
+    }
+
+    #[test]
+    fn time_mode_accrues_over_the_elapsed_seconds() {
+        // 0.0000001 per second
+        let mut deps = borrowed_market(100_000_000_000, false, AccrualMode::Time);
+        let start = mock_env("bob", &[]).block;
+
+        // New blocks without elapsed time accrue nothing
+        mint_later(&mut deps, 10, 0);
+        let state = get_state(&deps.storage).unwrap();
+        assert_eq!(state.block_number, start.height + 10);
+        assert_eq!(state.last_accrual_time, start.time);
+        assert_eq!(state.borrow_index, EXP_SCALE);
+        assert_eq!(state.total_borrows, 400_000_000);
+
+        // 6000 seconds accrue the same interest whether they took one block or a hundred
+        mint_later(&mut deps, 11, 6_000);
+        let state = get_state(&deps.storage).unwrap();
+        assert_eq!(state.last_accrual_time, start.time + 6_000);
+        assert_eq!(state.borrow_index, EXP_SCALE + EXP_SCALE * 6 / 10_000);
+        assert_eq!(state.total_borrows, 400_240_000);
+        assert_eq!(state.total_reserves, 24_000);
+
+        let mut deps = borrowed_market(100_000_000_000, false, AccrualMode::Time);
+        mint_later(&mut deps, 110, 6_000);
+        assert_eq!(get_state(&deps.storage).unwrap().borrow_index, EXP_SCALE + EXP_SCALE * 6 / 10_000);
+    }
+
+    /// Market where alice borrowed all she could and is underwater after 1000 blocks of interest.
+    /// Returns the market and the env of those 1000 blocks later.
+    fn underwater_market(liquidation_incentive: u128) -> (MockDeps, Env) {
+        let mut msg = mock_init_msg();
+        msg.interest_model = Some(InterestModel::Fixed { rate: msg.max_borrow_rate });
+        msg.liquidation_incentive = Uint128::from(liquidation_incentive);
+        let mut deps = init_market(msg).unwrap();
+        mint(&mut deps, "alice", 1_000_000).unwrap();
+        borrow(&mut deps, "alice", 500_000).unwrap();
+
+        // 0.5% of interest: alice owes 502500 against 1000000 cTokens worth 1.00225 each
+        let mut env = mock_env("bob", &[]);
+        env.block.height += 1_000;
+        (deps, env)
+    }
+
+    fn liquidate(deps: &mut MockDeps, mut env: Env, liquidator: &str, repay_amount: u128, sent: u128) -> StdResult<HandleResponse> {
+        env.message.sender = HumanAddr::from(liquidator);
+        let msg = HandleMsg::LiquidateBorrow {
+            borrower: HumanAddr::from("alice"),
+            repay_amount: Uint128::from(repay_amount),
+        };
+        run_at(deps, env, sent, msg)
+    }
+
+    #[test]
+    fn a_healthy_borrower_cannot_be_liquidated() {
+        let mut deps = mock_market(None);
+        mint(&mut deps, "alice", 1_000).unwrap();
+        borrow(&mut deps, "alice", 400).unwrap();
+
+        let env = mock_env("bob", &[]);
+        assert_generic_err(liquidate(&mut deps, env, "bob", 100, 100), "The borrower is not undercollateralized");
+    }
+
+    #[test]
+    fn a_borrower_cannot_liquidate_themselves() {
+        let (mut deps, env) = underwater_market(EXP_SCALE + EXP_SCALE / 10);
+        assert_generic_err(
+            liquidate(&mut deps, env, "alice", 100, 100),
+            "A borrower cannot liquidate their own borrow",
+        );
+    }
+
+    #[test]
+    fn a_liquidation_repays_at_most_the_close_factor() {
+        let (mut deps, env) = underwater_market(EXP_SCALE + EXP_SCALE / 10);
+        assert_generic_err(
+            liquidate(&mut deps, env.clone(), "bob", 251_251, 251_251),
+            "Liquidation repay amount exceeds the close factor: repay_amount: 251251, max_close: 251250",
+        );
+
+        liquidate(&mut deps, env, "bob", 251_250, 251_250).unwrap();
+        assert_eq!(get_account_borrow(&deps, &canonical(&deps, "alice")).unwrap(), 251_250);
+    }
+
+    #[test]
+    fn a_liquidation_cannot_seize_more_than_the_borrower_has() {
+        // An incentive of 10 would seize about 1995510 cTokens for a repay of 200000
+        let (mut deps, env) = underwater_market(EXP_SCALE * 10);
+        assert_generic_err(
+            liquidate(&mut deps, env, "bob", 200_000, 200_000),
+            "The borrower has insufficient collateral to seize",
+        );
+        assert_eq!(get_balance(&deps.storage, &canonical(&deps, "alice")).unwrap(), 1_000_000);
+    }
+
+    #[test]
+    fn underlying_sent_above_the_repay_amount_is_refunded() {
+        let (mut deps, env) = underwater_market(EXP_SCALE + EXP_SCALE / 10);
+        let response = liquidate(&mut deps, env.clone(), "bob", 100_000, 150_000).unwrap();
+
+        assert_eq!(
+            response.messages,
+            vec![CosmosMsg::Bank(BankMsg::Send {
+                from_address: env.contract.address,
+                to_address: HumanAddr::from("bob"),
+                amount: vec![Coin { denom: MOCK_DENOM.to_string(), amount: Uint128::from(50_000u128) }],
+            })]
+        );
+        assert_eq!(get_account_borrow(&deps, &canonical(&deps, "alice")).unwrap(), 402_500);
+    }
+
+    #[test]
+    fn redeeming_underlying_rounds_the_burnt_tokens_up() {
+        // One cToken is worth 3 of the underlying
+        let mut msg = mock_init_msg();
+        msg.initial_exchange_rate = Uint128::from(EXP_SCALE * 3);
+        let mut deps = init_market(msg).unwrap();
+        mint(&mut deps, "alice", 3_000).unwrap();
+        let alice = canonical(&deps, "alice");
+        assert_eq!(get_balance(&deps.storage, &alice).unwrap(), 1_000);
+
+        // 100 is worth 33.33 cTokens, so 34 are burnt
+        run(&mut deps, "alice", 0, HandleMsg::RedeemUnderlying { amount: Uint128::from(100u128) }).unwrap();
+        assert_eq!(get_balance(&deps.storage, &alice).unwrap(), 966);
+        assert_eq!(get_state(&deps.storage).unwrap().cash, 2_900);
+
+        run(&mut deps, "alice", 0, HandleMsg::RedeemUnderlying { amount: Uint128::from(300u128) }).unwrap();
+        assert_eq!(get_balance(&deps.storage, &alice).unwrap(), 866);
+    }
 }
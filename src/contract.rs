@@ -1,25 +1,37 @@
 //use serde::{Deserialize, Serialize};
 
 use cosmwasm_std::{
-    to_binary, Api, Env, Extern, HandleResponse, HumanAddr,
-    InitResponse, InitResult, Querier, QueryResult, StdResult, Storage, Uint128,
+    from_binary, log, to_binary, Api, Env, Extern, HandleResponse, HumanAddr,
+    InitResponse, InitResult, Querier, QueryResult, StdError, StdResult, Storage, Uint128,
 };
 
-//use std::collections::HashSet;
+use std::convert::TryFrom;
 
 //use serde_json_wasm as serde_json;
 
+use secret_toolkit::crypto::sha_256;
+use secret_toolkit::snip20::register_receive_msg;
 use secret_toolkit::utils::{pad_handle_result, pad_query_result};
 
+use lending_common::admin::{self, check_admin};
+use lending_common::exponential::{Exp, EXP_SCALE};
+
 use crate::msg::{
-    HandleMsg, InitMsg, QueryAnswer, QueryMsg,
+    ContractStatusLevel, HandleAnswer, HandleMsg, InitMsg, QueryAnswer, QueryMsg, QueryWithPermit,
+    ReceiveMsg, ResponseStatus,
 };
 use crate::state::{
-    save, get_allowance, get_balance ,get_config, Config, State,
-    CONFIG_KEY, STATE_KEY,
+    save, get_allowance, get_balance ,get_config, set_config, get_state, set_state, Config, State,
+    get_prng_seed, set_prng_seed, read_viewing_key, write_viewing_key, CONFIG_KEY, STATE_KEY,
 };
 
-use crate::{collateral, token};
+use crate::{collateral, history, token};
+use crate::interest_model::{
+    annual_percentage_yield, get_borrow_rate, get_supply_rate, get_utilization_rate, AccrualMode,
+    InterestModel, SECONDS_PER_BLOCK,
+};
+use crate::permit::{self, Permission, Permit};
+use crate::viewing_key::{ViewingKey, VIEWING_KEY_SIZE};
 
 
 
@@ -27,11 +39,48 @@ use crate::{collateral, token};
 /// response size
 pub const BLOCK_SIZE: usize = 256;
 
+/// upper bound of the collateral factor: 0.9 * 10^18
+pub const MAX_COLLATERAL_FACTOR: u128 = 900_000_000_000_000_000;
+
+/// upper bound of the max borrow rate per block: 0.0005% * 10^18, as in Compound. Markets accruing
+/// per second are bounded by the same rate spread over the average block time.
+pub const MAX_BORROW_RATE_PER_BLOCK: u128 = 5_000_000_000_000;
+
 pub fn init<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
     msg: InitMsg,
 ) -> InitResult {
+    validate_reserve_factor(msg.reserve_factor.u128())?;
+    let accrual_mode = msg.accrual_mode.unwrap_or_default();
+    validate_max_borrow_rate(msg.max_borrow_rate.u128(), accrual_mode)?;
+    let interest_model = msg.interest_model.unwrap_or_else(|| InterestModel::default_for(accrual_mode));
+    interest_model.validate()?;
+    validate_model_rate(&interest_model, msg.max_borrow_rate.u128())?;
+
+    if msg.close_factor.u128() > EXP_SCALE {
+        return Err(StdError::generic_err(format!(
+            "close factor must not exceed 1: close_factor: {}",
+            msg.close_factor)
+        )
+        );
+    }
+    if msg.liquidation_incentive.u128() < EXP_SCALE {
+        return Err(StdError::generic_err(format!(
+            "liquidation incentive must be at least 1: liquidation_incentive: {}",
+            msg.liquidation_incentive)
+        )
+        );
+    }
+
+    if msg.collateral_factor.u128() > MAX_COLLATERAL_FACTOR {
+        return Err(StdError::generic_err(format!(
+            "collateral factor is too high: collateral_factor: {}, max: {}",
+            msg.collateral_factor, MAX_COLLATERAL_FACTOR)
+        )
+        );
+    }
+
     let init_config = Config {
         name: msg.name,
         total_supply: msg.total_supply.u128(),
@@ -42,12 +91,38 @@ pub fn init<S: Storage, A: Api, Q: Querier>(
         reserve_factor: msg.reserve_factor.u128(),
         max_borrow_rate: msg.max_borrow_rate.u128(),
         borrow_index:msg.borrow_index.u128(),
+        close_factor: msg.close_factor.u128(),
+        liquidation_incentive: msg.liquidation_incentive.u128(),
+        collateral_factor: msg.collateral_factor.u128(),
+        controller: msg.controller,
+        underlying_token: msg.underlying_token,
+        admin: msg.admin.unwrap_or_else(|| env.message.sender.clone()),
+        pending_admin: None,
+        interest_model,
+        compound_interest: msg.compound_interest.unwrap_or(false),
+        accrual_mode,
+        contract_address: env.contract.address.clone(),
+        contract_status: ContractStatusLevel::NormalRun,
+        quiet_mode: msg.quiet_mode.unwrap_or(false),
     };
     save(&mut deps.storage, CONFIG_KEY, &init_config)?;
 
+    // Register with the underlying token so that deposits and repayments can be received
+    let mut messages = vec![];
+    if let Some(token) = &init_config.underlying_token {
+        messages.push(register_receive_msg(
+            env.contract_code_hash.clone(),
+            None,
+            BLOCK_SIZE,
+            token.code_hash.clone(),
+            token.address.clone(),
+        )?);
+    }
+
     let init_state = State {
         cash: 0u128,
         block_number: env.block.height,
+        last_accrual_time: env.block.time,
         total_reserves: 0u128,
         total_borrows: 0u128,
         exchange_rate: init_config.initial_exchange_rate,
@@ -57,7 +132,13 @@ pub fn init<S: Storage, A: Api, Q: Querier>(
     };
     save(&mut deps.storage, STATE_KEY, &init_state)?;
 
-    Ok(InitResponse::default())
+    let prng_seed_hashed = sha_256(&msg.prng_seed.0);
+    set_prng_seed(&mut deps.storage, &prng_seed_hashed)?;
+
+    Ok(InitResponse {
+        messages,
+        log: vec![],
+    })
 }
 
 pub fn handle<S: Storage, A: Api, Q: Querier>(
@@ -65,15 +146,39 @@ pub fn handle<S: Storage, A: Api, Q: Querier>(
     env: Env,
     msg: HandleMsg,
 ) -> StdResult<HandleResponse> {
+    let config = get_config(&deps.storage)?;
+    if !is_allowed_with_status(config.contract_status, &msg) {
+        return pad_handle_result(
+            Err(StdError::generic_err(
+                "This contract is stopped and this action is not allowed",
+            )),
+            BLOCK_SIZE,
+        );
+    }
+
+    // Funds sent along with any other action would be stuck in the contract
+    if !env.message.sent_funds.is_empty() && !takes_payment(&msg) {
+        return pad_handle_result(
+            Err(StdError::generic_err("This action does not accept any funds")),
+            BLOCK_SIZE,
+        );
+    }
+
     let response = match msg {
         HandleMsg::Mint {} => collateral::try_mint(deps, env),
-        HandleMsg::Redeem { redeem_tokens_in } => {
-            collateral::try_redeem(deps, env, redeem_tokens_in)
+        HandleMsg::Redeem { tokens } => {
+            collateral::try_redeem(deps, env, tokens)
+        },
+        HandleMsg::RedeemUnderlying { amount } => {
+            collateral::try_redeem_underlying(deps, env, amount)
         },
         HandleMsg::Borrow { borrow_amount } => {
             collateral::try_borrow(deps, env, borrow_amount)
         },
         HandleMsg::RepayBorrow {} => collateral::try_repay_borrow(deps, env),
+        HandleMsg::LiquidateBorrow { borrower, repay_amount } => {
+            collateral::try_liquidate_borrow(deps, env, borrower, repay_amount)
+        },
         HandleMsg::Approve { spender,amount } => {
             token::try_approve(deps, env, &spender, &amount)
         },
@@ -83,24 +188,404 @@ pub fn handle<S: Storage, A: Api, Q: Querier>(
         HandleMsg::TransferFrom { owner, recipient, amount } => {
             token::try_transfer_from(deps, env, &owner, &recipient, &amount)
         },
+        HandleMsg::Send { recipient, recipient_code_hash, amount, msg, .. } => {
+            token::try_send(deps, env, recipient, recipient_code_hash, amount, msg)
+        },
+        HandleMsg::SendFrom { owner, recipient, recipient_code_hash, amount, msg, .. } => {
+            token::try_send_from(deps, env, owner, recipient, recipient_code_hash, amount, msg)
+        },
+        HandleMsg::RegisterReceive { code_hash, .. } => {
+            token::try_register_receive(deps, env, code_hash)
+        },
+        HandleMsg::Burn { amount, .. } => token::try_burn(deps, env, amount),
+        HandleMsg::BurnFrom { owner, amount, .. } => token::try_burn_from(deps, env, owner, amount),
+        HandleMsg::IncreaseAllowance { spender, amount, expiration, .. } => {
+            token::try_increase_allowance(deps, env, spender, amount, expiration)
+        },
+        HandleMsg::DecreaseAllowance { spender, amount, expiration, .. } => {
+            token::try_decrease_allowance(deps, env, spender, amount, expiration)
+        },
+        HandleMsg::SetContractStatus { level, .. } => try_set_contract_status(deps, env, level),
+        HandleMsg::SetQuietMode { quiet_mode, .. } => try_set_quiet_mode(deps, env, quiet_mode),
+        HandleMsg::SetPendingAdmin { address } => try_set_pending_admin(deps, env, address),
+        HandleMsg::AcceptAdmin {} => try_accept_admin(deps, env),
+        HandleMsg::UpdateMarketParams { reserve_factor, max_borrow_rate, interest_model } => {
+            try_update_market_params(deps, env, reserve_factor, max_borrow_rate, interest_model)
+        },
+        HandleMsg::CreateViewingKey { entropy, .. } => try_create_key(deps, env, entropy),
+        HandleMsg::SetViewingKey { key, .. } => try_set_key(deps, env, key),
+        HandleMsg::RevokePermit { permit_name, .. } => try_revoke_permit(deps, env, permit_name),
+        HandleMsg::Receive { from, amount, msg, .. } => {
+            collateral::try_receive(deps, env, from, amount, msg)
+        },
+    };
+
+    // Even the action names can be dropped so that nothing about the message shows on chain.
+    // The mode is read after the handler ran, so the message that turns it on is already quiet.
+    let response = match response {
+        Ok(response) if get_config(&deps.storage)?.quiet_mode => {
+            Ok(HandleResponse { log: vec![], ..response })
+        },
+        response => response,
     };
     pad_handle_result(response, BLOCK_SIZE)
 }
 
+/// Returns true if `msg` can be handled while the market is in `status`. A stopped market still
+/// lets users pay back their loans and take their collateral out.
+fn is_allowed_with_status(status: ContractStatusLevel, msg: &HandleMsg) -> bool {
+    match status {
+        ContractStatusLevel::NormalRun => true,
+        ContractStatusLevel::StopAllButRedeems => match msg {
+            HandleMsg::SetContractStatus { .. }
+            | HandleMsg::Redeem { .. }
+            | HandleMsg::RedeemUnderlying { .. }
+            | HandleMsg::RepayBorrow { .. }
+            | HandleMsg::CreateViewingKey { .. }
+            | HandleMsg::SetViewingKey { .. }
+            | HandleMsg::RevokePermit { .. } => true,
+            HandleMsg::Receive { msg: Some(msg), .. } => {
+                matches!(from_binary::<ReceiveMsg>(msg), Ok(ReceiveMsg::RepayBorrow {}))
+            },
+            _ => false,
+        },
+        ContractStatusLevel::StopAll => matches!(msg, HandleMsg::SetContractStatus { .. }),
+    }
+}
+
+/// Returns whether `msg` is paid for with funds in the market denom
+fn takes_payment(msg: &HandleMsg) -> bool {
+    matches!(msg, HandleMsg::Mint {} | HandleMsg::RepayBorrow {} | HandleMsg::LiquidateBorrow { .. })
+}
+
+fn try_set_pending_admin<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    address: HumanAddr,
+) -> StdResult<HandleResponse> {
+    let mut config = get_config(&deps.storage)?;
+    let log = admin::set_pending_admin(&deps.api, &env, &config.admin, &mut config.pending_admin, address)?;
+    set_config(&mut deps.storage, &config)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log,
+        data: Some(to_binary(&HandleAnswer::SetPendingAdmin {
+            status: ResponseStatus::Success,
+        })?),
+    })
+}
+
+fn try_accept_admin<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+) -> StdResult<HandleResponse> {
+    let mut config = get_config(&deps.storage)?;
+    let log = admin::accept_admin(&env, &mut config.admin, &mut config.pending_admin)?;
+    set_config(&mut deps.storage, &config)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log,
+        data: Some(to_binary(&HandleAnswer::AcceptAdmin {
+            status: ResponseStatus::Success,
+        })?),
+    })
+}
+
+fn try_set_contract_status<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    level: ContractStatusLevel,
+) -> StdResult<HandleResponse> {
+    let mut config = get_config(&deps.storage)?;
+    check_admin(&config.admin, &env)?;
+
+    config.contract_status = level;
+    set_config(&mut deps.storage, &config)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![log("action", "set_contract_status")],
+        data: Some(to_binary(&HandleAnswer::SetContractStatus {
+            status: ResponseStatus::Success,
+        })?),
+    })
+}
+
+fn try_set_quiet_mode<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    quiet_mode: bool,
+) -> StdResult<HandleResponse> {
+    let mut config = get_config(&deps.storage)?;
+    check_admin(&config.admin, &env)?;
+
+    config.quiet_mode = quiet_mode;
+    set_config(&mut deps.storage, &config)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![log("action", "set_quiet_mode")],
+        data: Some(to_binary(&HandleAnswer::SetQuietMode {
+            status: ResponseStatus::Success,
+        })?),
+    })
+}
+
+fn try_update_market_params<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    reserve_factor: Option<Uint128>,
+    max_borrow_rate: Option<Uint128>,
+    interest_model: Option<InterestModel>,
+) -> StdResult<HandleResponse> {
+    let config = get_config(&deps.storage)?;
+    check_admin(&config.admin, &env)?;
+
+    // Interest up to this block accrues under the old parameters, capped by the old max rate
+    // in case they are being fixed
+    collateral::accrue_interest_at_most_max_rate(deps, env)?;
+
+    let mut new_config = get_config(&deps.storage)?;
+    let mut new_state = get_state(&deps.storage)?;
+    let mut logs = vec![log("action", "update_market_params")];
+
+    if let Some(reserve_factor) = reserve_factor {
+        validate_reserve_factor(reserve_factor.u128())?;
+        logs.push(log("old_reserve_factor", new_config.reserve_factor));
+        logs.push(log("new_reserve_factor", reserve_factor));
+        new_config.reserve_factor = reserve_factor.u128();
+        new_state.reserve_factor = reserve_factor.u128();
+    }
+    if let Some(max_borrow_rate) = max_borrow_rate {
+        validate_max_borrow_rate(max_borrow_rate.u128(), new_config.accrual_mode)?;
+        logs.push(log("old_max_borrow_rate", new_config.max_borrow_rate));
+        logs.push(log("new_max_borrow_rate", max_borrow_rate));
+        new_config.max_borrow_rate = max_borrow_rate.u128();
+        new_state.max_borrow_rate = max_borrow_rate.u128();
+    }
+    if let Some(interest_model) = interest_model {
+        interest_model.validate()?;
+        logs.push(log("old_interest_model", format!("{:?}", new_config.interest_model)));
+        logs.push(log("new_interest_model", format!("{:?}", interest_model)));
+        new_config.interest_model = interest_model;
+    }
+    validate_model_rate(&new_config.interest_model, new_config.max_borrow_rate)?;
+
+    set_config(&mut deps.storage, &new_config)?;
+    set_state(&mut deps.storage, &new_state)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: logs,
+        data: Some(to_binary(&HandleAnswer::UpdateMarketParams {
+            status: ResponseStatus::Success,
+        })?),
+    })
+}
+
+fn try_create_key<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    entropy: String,
+) -> StdResult<HandleResponse> {
+    let prng_seed = get_prng_seed(&deps.storage)?;
+    let key = ViewingKey::new(&env, &prng_seed, entropy.as_bytes());
+
+    let sender_raw = deps.api.canonical_address(&env.message.sender)?;
+    write_viewing_key(&mut deps.storage, &sender_raw, &key);
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::CreateViewingKey { key })?),
+    })
+}
+
+fn try_set_key<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    key: String,
+) -> StdResult<HandleResponse> {
+    let vk = ViewingKey(key);
+
+    let sender_raw = deps.api.canonical_address(&env.message.sender)?;
+    write_viewing_key(&mut deps.storage, &sender_raw, &vk);
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::SetViewingKey {
+            status: ResponseStatus::Success,
+        })?),
+    })
+}
+
+fn try_revoke_permit<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    permit_name: String,
+) -> StdResult<HandleResponse> {
+    permit::revoke_permit(&mut deps.storage, &env.message.sender, &permit_name);
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::RevokePermit {
+            status: ResponseStatus::Success,
+        })?),
+    })
+}
+
+fn validate_reserve_factor(reserve_factor: u128) -> StdResult<()> {
+    if reserve_factor > EXP_SCALE {
+        return Err(StdError::generic_err(format!(
+            "reserve factor must not exceed 1: reserve_factor: {}",
+            reserve_factor)
+        )
+        );
+    }
+    Ok(())
+}
+
+/// Returns an error if `interest_model` can ask for more than `max_borrow_rate`, which would
+/// stop the market from accruing interest
+fn validate_model_rate(interest_model: &InterestModel, max_borrow_rate: u128) -> StdResult<()> {
+    if let Some(model_rate) = interest_model.max_borrow_rate()? {
+        if model_rate > Exp::from_mantissa(max_borrow_rate) {
+            return Err(StdError::generic_err(format!(
+                "interest model rate can exceed the max borrow rate: max_model_rate: {}, max_borrow_rate: {}",
+                model_rate.mantissa(), max_borrow_rate)
+            )
+            );
+        }
+    }
+    Ok(())
+}
+
+fn validate_max_borrow_rate(max_borrow_rate: u128, accrual_mode: AccrualMode) -> StdResult<()> {
+    if max_borrow_rate == 0 {
+        return Err(StdError::generic_err("max borrow rate must be greater than zero"));
+    }
+    let upper_bound = match accrual_mode {
+        AccrualMode::Block => MAX_BORROW_RATE_PER_BLOCK,
+        AccrualMode::Time => MAX_BORROW_RATE_PER_BLOCK / SECONDS_PER_BLOCK,
+    };
+    if max_borrow_rate > upper_bound {
+        return Err(StdError::generic_err(format!(
+            "max borrow rate is too high: max_borrow_rate: {}, max: {}",
+            max_borrow_rate, upper_bound)
+        )
+        );
+    }
+    Ok(())
+}
+
 pub fn query<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>, msg: QueryMsg) -> QueryResult {
     let response = match msg {
         QueryMsg::Config {} => try_query_config(deps),
-        QueryMsg::Balance {
-            address,
-        } => try_query_balance(deps, &address),
-        QueryMsg::Allowance {
-            owner,
-            spender,
-        } => try_query_allowance(deps, &owner, &spender),
+        QueryMsg::TokenInfo {} => try_query_token_info(deps),
+        QueryMsg::ContractStatus {} => try_query_contract_status(deps),
+        QueryMsg::Rates {} => try_query_rates(deps),
+        QueryMsg::MarketState { at_height } => try_query_market_state(deps, at_height),
+        QueryMsg::WithPermit { permit, query } => permit_queries(deps, permit, query),
+        _ => viewing_keys_queries(deps, msg),
     };
     pad_query_result(response, BLOCK_SIZE)
 }
 
+/// Runs a query about an account once the viewing key has been checked against every address
+/// allowed to make it
+fn viewing_keys_queries<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    msg: QueryMsg,
+) -> QueryResult {
+    let (addresses, key) = msg.get_validation_params()?;
+
+    for address in addresses {
+        let canonical_addr = deps.api.canonical_address(address)?;
+
+        if let Some(expected_key) = read_viewing_key(&deps.storage, &canonical_addr) {
+            if key.check_viewing_key(expected_key.as_slice()) {
+                return match msg {
+                    QueryMsg::Balance { address, .. } => try_query_balance(deps, &address),
+                    QueryMsg::Allowance { owner, spender, .. } => {
+                        try_query_allowance(deps, &owner, &spender)
+                    },
+                    QueryMsg::BorrowBalance { address, .. } => try_query_borrow_balance(deps, &address),
+                    QueryMsg::TransferHistory { address, page, page_size, .. } => {
+                        try_query_transfer_history(deps, &address, page.unwrap_or(0), page_size)
+                    },
+                    QueryMsg::LendingHistory { address, page, page_size, .. } => {
+                        try_query_lending_history(deps, &address, page.unwrap_or(0), page_size)
+                    },
+                    _ => Err(StdError::generic_err("This query type does not require authentication")),
+                };
+            }
+        } else {
+            // Checking the key will take significant time. We don't want to exit immediately if it isn't set
+            // in a way which will allow to time the command and determine if a viewing key doesn't exist
+            key.check_viewing_key(&[0u8; VIEWING_KEY_SIZE]);
+        }
+    }
+
+    to_binary(&QueryAnswer::ViewingKeyError {
+        msg: "Wrong viewing key for this address or viewing key not set".to_string(),
+    })
+}
+
+/// Runs a query about the account that signed the permit, if the permit grants it
+fn permit_queries<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    permit: Permit,
+    query: QueryWithPermit,
+) -> QueryResult {
+    let config = get_config(&deps.storage)?;
+    let account = permit::validate(deps, &permit, &config.contract_address)?;
+
+    match query {
+        QueryWithPermit::Balance {} => {
+            check_permission(&permit, Permission::Balance)?;
+            try_query_balance(deps, &account)
+        },
+        QueryWithPermit::Allowance { owner, spender } => {
+            check_permission(&permit, Permission::Allowance)?;
+            if account != owner && account != spender {
+                return Err(StdError::generic_err(format!(
+                    "Cannot query allowance. Requires permit for either owner {:?} or spender {:?}, got permit for {:?}",
+                    owner.as_str(), spender.as_str(), account.as_str())
+                )
+                );
+            }
+            try_query_allowance(deps, &owner, &spender)
+        },
+        QueryWithPermit::BorrowBalance {} => {
+            check_permission(&permit, Permission::Balance)?;
+            try_query_borrow_balance(deps, &account)
+        },
+        QueryWithPermit::TransferHistory { page, page_size } => {
+            check_permission(&permit, Permission::History)?;
+            try_query_transfer_history(deps, &account, page.unwrap_or(0), page_size)
+        },
+        QueryWithPermit::LendingHistory { page, page_size } => {
+            check_permission(&permit, Permission::History)?;
+            try_query_lending_history(deps, &account, page.unwrap_or(0), page_size)
+        },
+    }
+}
+
+fn check_permission(permit: &Permit, permission: Permission) -> StdResult<()> {
+    if !permit.check_permission(&permission) {
+        return Err(StdError::generic_err(format!(
+            "No permission to query, got permissions {:?}",
+            permit.params.permissions)
+        )
+        );
+    }
+    Ok(())
+}
+
 fn try_query_config<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>) -> QueryResult {
     let config: Config = get_config(&deps.storage)?;
     to_binary(&QueryAnswer::ConfigResponse {
@@ -112,6 +597,106 @@ fn try_query_config<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>) -> Q
         reserve_factor: Uint128::from(config.reserve_factor),
         borrow_index: Uint128::from(config.borrow_index),
         denom: config.denom,
+        close_factor: Uint128::from(config.close_factor),
+        liquidation_incentive: Uint128::from(config.liquidation_incentive),
+        collateral_factor: Uint128::from(config.collateral_factor),
+        controller: config.controller,
+        underlying_token: config.underlying_token,
+        admin: config.admin,
+        pending_admin: config.pending_admin,
+        max_borrow_rate: Uint128::from(config.max_borrow_rate),
+        interest_model: config.interest_model,
+        compound_interest: config.compound_interest,
+        accrual_mode: config.accrual_mode,
+        quiet_mode: config.quiet_mode,
+    })
+}
+
+fn try_query_token_info<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>) -> QueryResult {
+    let config = get_config(&deps.storage)?;
+    to_binary(&QueryAnswer::TokenInfo {
+        name: config.name,
+        symbol: config.symbol,
+        decimals: config.decimals,
+        total_supply: Some(Uint128::from(config.total_supply)),
+    })
+}
+
+fn try_query_contract_status<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>) -> QueryResult {
+    let config = get_config(&deps.storage)?;
+    to_binary(&QueryAnswer::ContractStatus {
+        status: config.contract_status,
+    })
+}
+
+fn try_query_rates<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>) -> QueryResult {
+    let config = get_config(&deps.storage)?;
+    let state = get_state(&deps.storage)?;
+
+    let utilization_rate = get_utilization_rate(state.cash, state.total_borrows, state.total_reserves)?;
+    let borrow_rate = get_borrow_rate(
+        &deps.querier,
+        &config.interest_model,
+        state.cash,
+        state.total_borrows,
+        state.total_reserves,
+    )?;
+    let supply_rate = get_supply_rate(
+        &deps.querier,
+        &config.interest_model,
+        state.cash,
+        state.total_borrows,
+        state.total_reserves,
+        Exp::from_mantissa(state.reserve_factor),
+    )?;
+
+    to_binary(&QueryAnswer::RatesResponse {
+        utilization_rate: Uint128::from(utilization_rate),
+        borrow_rate: Uint128::from(borrow_rate),
+        supply_rate: Uint128::from(supply_rate),
+        borrow_apy: Uint128::from(annual_percentage_yield(borrow_rate, config.accrual_mode)?),
+        supply_apy: Uint128::from(annual_percentage_yield(supply_rate, config.accrual_mode)?),
+        accrual_mode: config.accrual_mode,
+    })
+}
+
+fn try_query_market_state<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    at_height: Option<u64>,
+) -> QueryResult {
+    let config = get_config(&deps.storage)?;
+    let mut state = get_state(&deps.storage)?;
+
+    // Simulate the accrual up to the requested height without saving anything
+    if let Some(height) = at_height {
+        if height < state.block_number {
+            return Err(StdError::generic_err(format!(
+                "Cannot project the market state to a past height: at_height: {}, market_block: {}",
+                height, state.block_number)
+            )
+            );
+        }
+        // Queries have no block time, so time-based markets assume the average block time
+        let elapsed_seconds = u64::try_from(
+            u128::from(height - state.block_number) * SECONDS_PER_BLOCK,
+        ).map_err(|_| StdError::generic_err("Projected height is too far in the future"))?;
+        let time = state.last_accrual_time.checked_add(elapsed_seconds).ok_or_else(|| {
+            StdError::generic_err("Projected height is too far in the future")
+        })?;
+        state = collateral::calculate_accrued_state(&deps.querier, &config, &state, height, time)?;
+    }
+
+    let exchange_rate = collateral::calculate_exchange_rate(&config, &state)?;
+
+    to_binary(&QueryAnswer::MarketStateResponse {
+        cash: Uint128::from(state.cash),
+        total_borrows: Uint128::from(state.total_borrows),
+        total_reserves: Uint128::from(state.total_reserves),
+        total_supply: Uint128::from(config.total_supply),
+        borrow_index: Uint128::from(state.borrow_index),
+        exchange_rate: Uint128::from(exchange_rate),
+        accrual_block_number: state.block_number,
+        accrual_time: state.last_accrual_time,
     })
 }
 
@@ -126,6 +711,46 @@ fn try_query_balance<S: Storage, A: Api, Q: Querier>(
     })
 }
 
+fn try_query_borrow_balance<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    address: &HumanAddr,
+) -> QueryResult {
+    let address_key = deps.api.canonical_address(address)?;
+    let borrow_balance = collateral::get_account_borrow(deps, &address_key)?;
+    to_binary(&QueryAnswer::BorrowBalanceResponse {
+        borrow_balance: Uint128::from(borrow_balance),
+    })
+}
+
+fn try_query_transfer_history<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    address: &HumanAddr,
+    page: u32,
+    page_size: u32,
+) -> QueryResult {
+    let address_key = deps.api.canonical_address(address)?;
+    let (txs, total) = history::get_txs(&deps.api, &deps.storage, &address_key, page, page_size)?;
+    to_binary(&QueryAnswer::TransferHistory {
+        txs,
+        total: Some(total),
+    })
+}
+
+fn try_query_lending_history<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    address: &HumanAddr,
+    page: u32,
+    page_size: u32,
+) -> QueryResult {
+    let address_key = deps.api.canonical_address(address)?;
+    let (events, total) =
+        history::get_lending_events(&deps.api, &deps.storage, &address_key, page, page_size)?;
+    to_binary(&QueryAnswer::LendingHistory {
+        events,
+        total: Some(total),
+    })
+}
+
 fn try_query_allowance<S: Storage, A: Api, Q: Querier>(
     deps: &Extern<S, A, Q>,
     owner: &HumanAddr,
@@ -135,6 +760,341 @@ fn try_query_allowance<S: Storage, A: Api, Q: Querier>(
     let spender_key = deps.api.canonical_address(&spender)?;
     let allowance = get_allowance(&deps.storage, &owner_key, &spender_key)?;
     to_binary(&QueryAnswer::AllowanceResponse {
-        allowance: Uint128::from(allowance),
+        spender: spender.clone(),
+        owner: owner.clone(),
+        allowance: Uint128::from(allowance.amount),
+        expiration: allowance.expiration,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmwasm_std::testing::mock_env;
+    use cosmwasm_std::Binary;
+
+    use crate::msg::Contract;
+    use crate::testing::{
+        account_borrow, assert_generic_err, assert_unauthorized, borrow, init_market, mint, mock_controller, mock_init_msg,
+        mock_interest_model, mock_market, redeem, repay, run, run_at, transfer, MockDeps,
+    };
+
+    fn update_max_borrow_rate(deps: &mut MockDeps, max_borrow_rate: u128) -> StdResult<HandleResponse> {
+        let msg = HandleMsg::UpdateMarketParams {
+            reserve_factor: None,
+            max_borrow_rate: Some(Uint128::from(max_borrow_rate)),
+            interest_model: None,
+        };
+        run(deps, "admin", 0, msg)
+    }
+
+    #[test]
+    fn max_borrow_rate_per_block_is_bounded() {
+        let mut msg = mock_init_msg();
+        msg.max_borrow_rate = Uint128::from(MAX_BORROW_RATE_PER_BLOCK + 1);
+        assert_generic_err(init_market(msg).map(|_| ()), "max borrow rate is too high");
+
+        let mut msg = mock_init_msg();
+        msg.max_borrow_rate = Uint128::from(MAX_BORROW_RATE_PER_BLOCK);
+        let mut deps = init_market(msg).unwrap();
+
+        assert_generic_err(
+            update_max_borrow_rate(&mut deps, MAX_BORROW_RATE_PER_BLOCK + 1),
+            "max borrow rate is too high",
+        );
+        assert_generic_err(update_max_borrow_rate(&mut deps, 0), "max borrow rate must be greater than zero");
+        let default_model_rate = InterestModel::default().max_borrow_rate().unwrap().unwrap();
+        update_max_borrow_rate(&mut deps, default_model_rate.mantissa()).unwrap();
+        update_max_borrow_rate(&mut deps, MAX_BORROW_RATE_PER_BLOCK).unwrap();
+        assert_eq!(get_config(&deps.storage).unwrap().max_borrow_rate, MAX_BORROW_RATE_PER_BLOCK);
+    }
+
+    #[test]
+    fn max_borrow_rate_per_second_is_bounded() {
+        let max_per_second = MAX_BORROW_RATE_PER_BLOCK / SECONDS_PER_BLOCK;
+
+        let mut msg = mock_init_msg();
+        msg.accrual_mode = Some(AccrualMode::Time);
+        msg.max_borrow_rate = Uint128::from(max_per_second + 1);
+        assert_generic_err(init_market(msg).map(|_| ()), "max borrow rate is too high");
+
+        let mut msg = mock_init_msg();
+        msg.accrual_mode = Some(AccrualMode::Time);
+        msg.max_borrow_rate = Uint128::from(max_per_second);
+        let mut deps = init_market(msg).unwrap();
+
+        assert_generic_err(update_max_borrow_rate(&mut deps, max_per_second + 1), "max borrow rate is too high");
+        update_max_borrow_rate(&mut deps, max_per_second).unwrap();
+    }
+
+    fn update_interest_model(deps: &mut MockDeps, env: Env, interest_model: InterestModel) -> StdResult<HandleResponse> {
+        let msg = HandleMsg::UpdateMarketParams {
+            reserve_factor: None,
+            max_borrow_rate: None,
+            interest_model: Some(interest_model),
+        };
+        run_at(deps, env, 0, msg)
+    }
+
+    #[test]
+    fn the_model_cannot_ask_for_more_than_the_max_borrow_rate() {
+        let too_high = "interest model rate can exceed the max borrow rate";
+        let mut msg = mock_init_msg();
+        msg.interest_model = Some(InterestModel::Fixed { rate: Uint128::from(msg.max_borrow_rate.u128() + 1) });
+        assert_generic_err(init_market(msg).map(|_| ()), too_high);
+
+        let mut msg = mock_init_msg();
+        msg.interest_model = Some(InterestModel::Fixed { rate: Uint128::from(1_000_000_000_000u128) });
+        let mut deps = init_market(msg).unwrap();
+        let env = mock_env("admin", &[]);
+
+        assert_generic_err(update_max_borrow_rate(&mut deps, 999_999_999_999), too_high);
+        update_max_borrow_rate(&mut deps, 1_000_000_000_000).unwrap();
+
+        // The jump multiplier only takes the rate over the max above the kink
+        let jump_rate = |jump_multiplier: u128| InterestModel::JumpRate {
+            base_rate: Uint128::from(0u128),
+            multiplier: Uint128::from(1_000_000_000_000u128),
+            jump_multiplier: Uint128::from(jump_multiplier),
+            kink: Uint128::from(EXP_SCALE * 8 / 10),
+        };
+        assert_generic_err(update_interest_model(&mut deps, env.clone(), jump_rate(1_000_000_000_005)), too_high);
+        update_interest_model(&mut deps, env, jump_rate(1_000_000_000_000)).unwrap();
+        assert_eq!(get_config(&deps.storage).unwrap().interest_model, jump_rate(1_000_000_000_000));
+    }
+
+    #[test]
+    fn the_admin_can_fix_a_model_asking_for_more_than_the_max_borrow_rate() {
+        let mut msg = mock_init_msg();
+        msg.interest_model = Some(InterestModel::External { contract: mock_interest_model() });
+        let mut deps = init_market(msg).unwrap();
+        mint(&mut deps, "alice", 1_000).unwrap();
+        borrow(&mut deps, "alice", 300).unwrap();
+
+        // The external model cannot be checked up front
+        let max_borrow_rate = get_config(&deps.storage).unwrap().max_borrow_rate;
+        deps.querier.set_rates(Uint128::from(max_borrow_rate * 2), Uint128::from(0u128));
+        let mut env = mock_env("alice", &[]);
+        env.block.height += 100;
+        assert_generic_err(run_at(&mut deps, env.clone(), 10, HandleMsg::Mint {}), "borrow rate is absurdly high");
+
+        // The interest accrued until the fix is capped by the max borrow rate
+        env.message.sender = HumanAddr::from("admin");
+        let fixed = InterestModel::Fixed { rate: Uint128::from(max_borrow_rate) };
+        update_interest_model(&mut deps, env.clone(), fixed).unwrap();
+        let state = get_state(&deps.storage).unwrap();
+        assert_eq!(state.block_number, env.block.height);
+        assert_eq!(state.borrow_index, EXP_SCALE + max_borrow_rate * 100);
+
+        env.block.height += 1;
+        env.message.sender = HumanAddr::from("alice");
+        run_at(&mut deps, env, 10, HandleMsg::Mint {}).unwrap();
+    }
+
+    /// Returns the utilization, borrow rate, supply rate, borrow APY and supply APY mantissas
+    fn query_rates(deps: &MockDeps) -> (u128, u128, u128, u128, u128) {
+        match from_binary(&query(deps, QueryMsg::Rates {}).unwrap()).unwrap() {
+            QueryAnswer::RatesResponse { utilization_rate, borrow_rate, supply_rate, borrow_apy, supply_apy, .. } => {
+                (utilization_rate.u128(), borrow_rate.u128(), supply_rate.u128(), borrow_apy.u128(), supply_apy.u128())
+            },
+            other => panic!("unexpected answer: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn rates_follow_the_default_jump_rate_model() {
+        let mut deps = init_market(mock_init_msg()).unwrap();
+        assert_eq!(query_rates(&deps), (0, 0, 0, 0, 0));
+
+        // Below the kink: 0.3 * 0.000000237823 per block, of which suppliers get 0.3 * 0.9
+        mint(&mut deps, "alice", 1_000_000_000).unwrap();
+        borrow(&mut deps, "alice", 300_000_000).unwrap();
+        assert_eq!(
+            query_rates(&deps),
+            (EXP_SCALE * 3 / 10, 71_346_900_000, 19_263_663_000, 454_710_340_291_425_426, 106_537_501_725_374_654)
+        );
+
+        // Above the kink: 0.8 * 0.000000237823 + 0.1 * 0.000000518455 per block
+        let mut deps = mock_market(Some(mock_controller()));
+        mint(&mut deps, "alice", 1_000_000_000).unwrap();
+        borrow(&mut deps, "alice", 900_000_000).unwrap();
+        assert_eq!(
+            query_rates(&deps),
+            (EXP_SCALE * 9 / 10, 242_103_900_000, 196_104_159_000, 2_561_867_862_572_290_923, 1_799_024_220_560_085_899)
+        );
+    }
+
+    #[test]
+    fn market_state_is_projected_like_a_real_accrual() {
+        let mut deps = init_market(mock_init_msg()).unwrap();
+        mint(&mut deps, "alice", 1_000_000_000).unwrap();
+        borrow(&mut deps, "alice", 300_000_000).unwrap();
+        let mut env = mock_env("alice", &[]);
+        let market_state = |deps: &MockDeps, at_height: Option<u64>| query(deps, QueryMsg::MarketState { at_height });
+
+        assert_generic_err(
+            market_state(&deps, Some(env.block.height - 1)),
+            "Cannot project the market state to a past height",
+        );
+        assert_eq!(market_state(&deps, Some(env.block.height)).unwrap(), market_state(&deps, None).unwrap());
+
+        env.block.height += 1_000;
+        env.block.time += 1_000 * SECONDS_PER_BLOCK as u64;
+        let projected = market_state(&deps, Some(env.block.height)).unwrap();
+        match from_binary(&projected).unwrap() {
+            QueryAnswer::MarketStateResponse { borrow_index, total_borrows, .. } => {
+                assert!(borrow_index.u128() > EXP_SCALE);
+                assert!(total_borrows.u128() > 300_000_000);
+            },
+            other => panic!("unexpected answer: {:?}", other),
+        }
+        // The projection does not save anything
+        assert_eq!(get_state(&deps.storage).unwrap().borrow_index, EXP_SCALE);
+
+        collateral::accrue_interest(&mut deps, env).unwrap();
+        assert_eq!(market_state(&deps, None).unwrap(), projected);
+    }
+
+    #[test]
+    fn rates_are_queried_from_an_external_model() {
+        let mut msg = mock_init_msg();
+        msg.interest_model = Some(InterestModel::External { contract: mock_interest_model() });
+        let mut deps = init_market(msg).unwrap();
+        deps.querier.set_rates(Uint128::from(3_000_000_000u128), Uint128::from(1_000_000_000u128));
+
+        let rates: QueryAnswer = from_binary(&query(&deps, QueryMsg::Rates {}).unwrap()).unwrap();
+        match rates {
+            QueryAnswer::RatesResponse { borrow_rate, supply_rate, .. } => {
+                assert_eq!(borrow_rate, Uint128::from(3_000_000_000u128));
+                assert_eq!(supply_rate, Uint128::from(1_000_000_000u128));
+            },
+            other => panic!("unexpected answer: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn balance_needs_the_viewing_key_of_the_account() {
+        let mut deps = init_market(mock_init_msg()).unwrap();
+        let query_balance = |deps: &MockDeps, key: &str| -> QueryAnswer {
+            let msg = QueryMsg::Balance { address: HumanAddr::from("alice"), key: key.to_string() };
+            from_binary(&query(deps, msg).unwrap()).unwrap()
+        };
+
+        match query_balance(&deps, "alice_key") {
+            QueryAnswer::ViewingKeyError { .. } => {},
+            other => panic!("unexpected answer: {:?}", other),
+        }
+
+        let msg = HandleMsg::SetViewingKey { key: "alice_key".to_string(), padding: None };
+        run(&mut deps, "alice", 0, msg).unwrap();
+
+        match query_balance(&deps, "wrong_key") {
+            QueryAnswer::ViewingKeyError { .. } => {},
+            other => panic!("unexpected answer: {:?}", other),
+        }
+        match query_balance(&deps, "alice_key") {
+            QueryAnswer::BalanceResponse { balance } => assert_eq!(balance, Uint128::from(0u128)),
+            other => panic!("unexpected answer: {:?}", other),
+        }
+    }
+
+    fn receive(deps: &mut MockDeps, amount: u128, msg: Option<Binary>) -> StdResult<HandleResponse> {
+        let msg = HandleMsg::Receive {
+            sender: HumanAddr::from("alice"),
+            from: HumanAddr::from("alice"),
+            amount: Uint128::from(amount),
+            msg,
+        };
+        handle(deps, mock_env("token", &[]), msg)
+    }
+
+    #[test]
+    fn stop_all_but_redeems_only_lets_repayments_through_receive() {
+        let mut msg = mock_init_msg();
+        msg.underlying_token = Some(Contract {
+            address: HumanAddr::from("token"),
+            code_hash: "token_code_hash".to_string(),
+        });
+        let mut deps = init_market(msg).unwrap();
+        receive(&mut deps, 1_000, Some(to_binary(&ReceiveMsg::Mint {}).unwrap())).unwrap();
+        borrow(&mut deps, "alice", 400).unwrap();
+
+        let msg = HandleMsg::SetContractStatus {
+            level: ContractStatusLevel::StopAllButRedeems,
+            padding: None,
+        };
+        run(&mut deps, "admin", 0, msg).unwrap();
+
+        let stopped = "This contract is stopped and this action is not allowed";
+        assert_generic_err(receive(&mut deps, 100, Some(to_binary(&ReceiveMsg::Mint {}).unwrap())), stopped);
+        let liquidate = ReceiveMsg::LiquidateBorrow {
+            borrower: HumanAddr::from("alice"),
+            repay_amount: Uint128::from(100u128),
+        };
+        assert_generic_err(receive(&mut deps, 100, Some(to_binary(&liquidate).unwrap())), stopped);
+        assert_generic_err(receive(&mut deps, 100, None), stopped);
+        assert_generic_err(borrow(&mut deps, "alice", 100), stopped);
+
+        receive(&mut deps, 100, Some(to_binary(&ReceiveMsg::RepayBorrow {}).unwrap())).unwrap();
+        assert_eq!(account_borrow(&deps, "alice"), 300);
+    }
+
+    /// Returns the log of `response` without the padding of its values
+    fn log_values(response: &HandleResponse) -> Vec<(&str, &str)> {
+        response.log.iter().map(|attr| (attr.key.as_str(), attr.value.trim_end())).collect()
+    }
+
+    #[test]
+    fn only_payments_accept_funds() {
+        let mut deps = init_market(mock_init_msg()).unwrap();
+        mint(&mut deps, "alice", 1_000).unwrap();
+
+        let no_funds = "This action does not accept any funds";
+        assert_generic_err(run(&mut deps, "alice", 10, HandleMsg::Borrow { borrow_amount: Uint128::from(100u128) }), no_funds);
+        assert_generic_err(run(&mut deps, "alice", 10, HandleMsg::Redeem { tokens: Uint128::from(100u128) }), no_funds);
+        let transfer = HandleMsg::Transfer { recipient: HumanAddr::from("bob"), amount: Uint128::from(100u128) };
+        assert_generic_err(run(&mut deps, "alice", 10, transfer), no_funds);
+        let approve = HandleMsg::Approve { spender: HumanAddr::from("bob"), amount: Uint128::from(100u128) };
+        assert_generic_err(run(&mut deps, "alice", 10, approve), no_funds);
+        let set_quiet_mode = HandleMsg::SetQuietMode { quiet_mode: true, padding: None };
+        assert_generic_err(run(&mut deps, "admin", 10, set_quiet_mode), no_funds);
+        assert_eq!(account_borrow(&deps, "alice"), 0);
+
+        // Payments still go through
+        borrow(&mut deps, "alice", 100).unwrap();
+        repay(&mut deps, "alice", 100).unwrap();
+        mint(&mut deps, "alice", 10).unwrap();
+    }
+
+    #[test]
+    fn quiet_mode_strips_every_log() {
+        let mut msg = mock_init_msg();
+        msg.quiet_mode = Some(true);
+        let mut deps = init_market(msg).unwrap();
+        let set_quiet_mode = |quiet_mode: bool| HandleMsg::SetQuietMode { quiet_mode, padding: None };
+
+        let set_key = HandleMsg::SetViewingKey { key: "key".to_string(), padding: None };
+        let responses = vec![
+            mint(&mut deps, "alice", 1_000).unwrap(),
+            borrow(&mut deps, "alice", 300).unwrap(),
+            repay(&mut deps, "alice", 100).unwrap(),
+            redeem(&mut deps, "alice", 100).unwrap(),
+            transfer(&mut deps, "alice", "bob", 100).unwrap(),
+            run(&mut deps, "alice", 0, set_key).unwrap(),
+        ];
+        for response in responses {
+            assert!(response.log.is_empty(), "unexpected log: {:?}", response.log);
+            assert!(response.data.is_some());
+        }
+
+        assert_unauthorized(run(&mut deps, "alice", 0, set_quiet_mode(false)));
+
+        // Turning quiet mode off already logs, turning it back on is already quiet
+        let response = run(&mut deps, "admin", 0, set_quiet_mode(false)).unwrap();
+        assert_eq!(log_values(&response), vec![("action", "set_quiet_mode")]);
+        let response = mint(&mut deps, "alice", 1_000).unwrap();
+        assert_eq!(log_values(&response), vec![("action", "mint")]);
+        let response = run(&mut deps, "admin", 0, set_quiet_mode(true)).unwrap();
+        assert!(response.log.is_empty());
+    }
+}
@@ -0,0 +1,481 @@
+// Risk checks that guard every action of the market.
+// When the market is configured with a controller contract the decision is delegated to it, so
+// several markets can share one risk manager. Otherwise the market checks the account liquidity
+// of its own cTokens.
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use cosmwasm_std::{
+    Api, CanonicalAddr, Env, Extern, HumanAddr, Querier, StdError, StdResult, Storage, Uint128,
+};
+
+use secret_toolkit::utils::Query;
+
+use lending_common::exponential::{add_uint, Exp, Rounding};
+
+use crate::collateral::{get_account_borrow, get_exchange_rate};
+use crate::contract::BLOCK_SIZE;
+use crate::msg::Contract;
+use crate::state::{get_balance, get_config};
+
+/// queries a market sends to its controller contract
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ControllerQueryMsg {
+    MintAllowed {
+        market: HumanAddr,
+        minter: HumanAddr,
+        mint_amount: Uint128,
+    },
+    RedeemAllowed {
+        market: HumanAddr,
+        redeemer: HumanAddr,
+        redeem_tokens: Uint128,
+    },
+    BorrowAllowed {
+        market: HumanAddr,
+        borrower: HumanAddr,
+        borrow_amount: Uint128,
+    },
+    RepayAllowed {
+        market: HumanAddr,
+        payer: HumanAddr,
+        borrower: HumanAddr,
+        repay_amount: Uint128,
+    },
+    TransferAllowed {
+        market: HumanAddr,
+        src: HumanAddr,
+        dst: HumanAddr,
+        transfer_tokens: Uint128,
+    },
+    LiquidateBorrowAllowed {
+        market: HumanAddr,
+        liquidator: HumanAddr,
+        borrower: HumanAddr,
+        repay_amount: Uint128,
+    },
+    SeizeAllowed {
+        market: HumanAddr,
+        liquidator: HumanAddr,
+        borrower: HumanAddr,
+        seize_tokens: Uint128,
+    },
+}
+
+impl Query for ControllerQueryMsg {
+    const BLOCK_SIZE: usize = BLOCK_SIZE;
+}
+
+/// controller answer to any of the `*Allowed` queries
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AllowedResponse {
+    pub allowed: bool,
+    pub reason: Option<String>,
+}
+
+/// Returns the (liquidity, shortfall) of `account` if it were to redeem `redeem_tokens` cTokens
+/// and borrow `borrow_amount` more of the underlying. At most one of the two is non-zero.
+///
+/// # Arguments
+///
+/// * `deps` - a reference to Extern containing all the contract's external dependencies
+/// * `env` - Env of the contract's environment
+/// * `account` - a reference to the canonical address of the account to check
+/// * `redeem_tokens` - amount of cTokens the account is hypothetically redeeming
+/// * `borrow_amount` - amount of underlying the account is hypothetically borrowing
+pub fn get_hypothetical_account_liquidity<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    env: Env,
+    account: &CanonicalAddr,
+    redeem_tokens: u128,
+    borrow_amount: u128,
+) -> StdResult<(u128, u128)> {
+    let config = get_config(&deps.storage)?;
+    let exchange_rate = get_exchange_rate(deps, env)?;
+    let balance = get_balance(&deps.storage, account)?;
+    let account_borrow = get_account_borrow(deps, account)?;
+
+    // Value of one cToken as collateral, in the underlying
+    let tokens_to_denom = exchange_rate.checked_mul(Exp::from_mantissa(config.collateral_factor), Rounding::Down)?;
+
+    let sum_collateral = tokens_to_denom.mul_scalar_to_int(balance, Rounding::Down)?;
+    let sum_borrow_plus_effects = add_uint(
+        add_uint(account_borrow, tokens_to_denom.mul_scalar_to_int(redeem_tokens, Rounding::Up)?)?,
+        borrow_amount,
+    )?;
+
+    if sum_collateral > sum_borrow_plus_effects {
+        Ok((sum_collateral - sum_borrow_plus_effects, 0))
+    } else {
+        Ok((0, sum_borrow_plus_effects - sum_collateral))
+    }
+}
+
+/// Returns an error if `minter` is not allowed to supply `mint_amount` of the underlying
+pub fn mint_allowed<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    env: Env,
+    minter: &HumanAddr,
+    mint_amount: u128,
+) -> StdResult<()> {
+    let config = get_config(&deps.storage)?;
+    match config.controller {
+        Some(controller) => query_allowed(deps, &controller, "mint", ControllerQueryMsg::MintAllowed {
+            market: env.contract.address,
+            minter: minter.clone(),
+            mint_amount: Uint128::from(mint_amount),
+        }),
+        // Supplying never puts an account at risk
+        None => Ok(()),
+    }
+}
+
+/// Returns an error if `redeemer` cannot give up `redeem_tokens` cTokens without a shortfall
+pub fn redeem_allowed<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    env: Env,
+    redeemer: &HumanAddr,
+    redeem_tokens: u128,
+) -> StdResult<()> {
+    let config = get_config(&deps.storage)?;
+    if let Some(controller) = config.controller {
+        return query_allowed(deps, &controller, "redeem", ControllerQueryMsg::RedeemAllowed {
+            market: env.contract.address,
+            redeemer: redeemer.clone(),
+            redeem_tokens: Uint128::from(redeem_tokens),
+        });
+    }
+
+    // Accounts without a borrow can always withdraw their own collateral
+    let redeemer_raw = deps.api.canonical_address(redeemer)?;
+    if get_account_borrow(deps, &redeemer_raw)? == 0 {
+        return Ok(());
+    }
+
+    let (_, shortfall) = get_hypothetical_account_liquidity(deps, env, &redeemer_raw, redeem_tokens, 0)?;
+    if shortfall > 0 {
+        return Err(StdError::generic_err(format!(
+            "Insufficient collateral to redeem: redeem_tokens: {}, shortfall: {}",
+            redeem_tokens, shortfall)
+        )
+        );
+    }
+    Ok(())
+}
+
+/// Returns an error if `borrower` cannot borrow `borrow_amount` without a shortfall
+pub fn borrow_allowed<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    env: Env,
+    borrower: &HumanAddr,
+    borrow_amount: u128,
+) -> StdResult<()> {
+    let config = get_config(&deps.storage)?;
+    if let Some(controller) = config.controller {
+        return query_allowed(deps, &controller, "borrow", ControllerQueryMsg::BorrowAllowed {
+            market: env.contract.address,
+            borrower: borrower.clone(),
+            borrow_amount: Uint128::from(borrow_amount),
+        });
+    }
+
+    let borrower_raw = deps.api.canonical_address(borrower)?;
+    let (_, shortfall) = get_hypothetical_account_liquidity(deps, env, &borrower_raw, 0, borrow_amount)?;
+    if shortfall > 0 {
+        return Err(StdError::generic_err(format!(
+            "Insufficient collateral to borrow: borrow_amount: {}, shortfall: {}",
+            borrow_amount, shortfall)
+        )
+        );
+    }
+    Ok(())
+}
+
+/// Returns an error if `payer` is not allowed to repay `repay_amount` of the borrow of `borrower`
+pub fn repay_allowed<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    env: Env,
+    payer: &HumanAddr,
+    borrower: &HumanAddr,
+    repay_amount: u128,
+) -> StdResult<()> {
+    let config = get_config(&deps.storage)?;
+    match config.controller {
+        Some(controller) => query_allowed(deps, &controller, "repay", ControllerQueryMsg::RepayAllowed {
+            market: env.contract.address,
+            payer: payer.clone(),
+            borrower: borrower.clone(),
+            repay_amount: Uint128::from(repay_amount),
+        }),
+        // Paying back a borrow never puts an account at risk
+        None => Ok(()),
+    }
+}
+
+/// Returns an error if `src` cannot transfer `transfer_tokens` cTokens to `dst` without a shortfall
+pub fn transfer_allowed<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    env: Env,
+    src: &HumanAddr,
+    dst: &HumanAddr,
+    transfer_tokens: u128,
+) -> StdResult<()> {
+    let config = get_config(&deps.storage)?;
+    if let Some(controller) = config.controller {
+        return query_allowed(deps, &controller, "transfer", ControllerQueryMsg::TransferAllowed {
+            market: env.contract.address,
+            src: src.clone(),
+            dst: dst.clone(),
+            transfer_tokens: Uint128::from(transfer_tokens),
+        });
+    }
+
+    // Transferring cTokens away is the same as redeeming them as far as collateral is concerned
+    redeem_allowed(deps, env, src, transfer_tokens)
+}
+
+/// Returns an error if `liquidator` cannot repay `repay_amount` of the borrow of `borrower`,
+/// i.e. unless `borrower` has a shortfall
+pub fn liquidate_borrow_allowed<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    env: Env,
+    liquidator: &HumanAddr,
+    borrower: &HumanAddr,
+    repay_amount: u128,
+) -> StdResult<()> {
+    let config = get_config(&deps.storage)?;
+    if let Some(controller) = config.controller {
+        // The controller sees the borrower's positions in every market, so it decides alone
+        return query_allowed(deps, &controller, "liquidation", ControllerQueryMsg::LiquidateBorrowAllowed {
+            market: env.contract.address,
+            liquidator: liquidator.clone(),
+            borrower: borrower.clone(),
+            repay_amount: Uint128::from(repay_amount),
+        });
+    }
+
+    // Only undercollateralized borrowers can be liquidated
+    let borrower_raw = deps.api.canonical_address(borrower)?;
+    let (_, shortfall) = get_hypothetical_account_liquidity(deps, env, &borrower_raw, 0, 0)?;
+    if shortfall == 0 {
+        return Err(StdError::generic_err("The borrower is not undercollateralized"));
+    }
+    Ok(())
+}
+
+/// Returns an error if `liquidator` is not allowed to seize `seize_tokens` cTokens of `borrower`
+pub fn seize_allowed<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    env: Env,
+    liquidator: &HumanAddr,
+    borrower: &HumanAddr,
+    seize_tokens: u128,
+) -> StdResult<()> {
+    let config = get_config(&deps.storage)?;
+    match config.controller {
+        Some(controller) => query_allowed(deps, &controller, "seizure", ControllerQueryMsg::SeizeAllowed {
+            market: env.contract.address,
+            liquidator: liquidator.clone(),
+            borrower: borrower.clone(),
+            seize_tokens: Uint128::from(seize_tokens),
+        }),
+        // The liquidation was allowed, seizing is part of it
+        None => Ok(()),
+    }
+}
+
+/// Asks the controller contract whether `action` is allowed and turns a refusal into an error
+fn query_allowed<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    controller: &Contract,
+    action: &str,
+    msg: ControllerQueryMsg,
+) -> StdResult<()> {
+    let response: AllowedResponse =
+        msg.query(&deps.querier, controller.code_hash.clone(), controller.address.clone())?;
+    if !response.allowed {
+        return Err(StdError::generic_err(format!(
+            "The controller rejected the {}: {}",
+            action,
+            response.reason.unwrap_or_else(|| "no reason given".to_string()))
+        )
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmwasm_std::testing::mock_env;
+    use cosmwasm_std::HandleResponse;
+
+    use crate::msg::HandleMsg;
+    use crate::testing::{
+        account_borrow, assert_generic_err, balance, borrow, canonical, mint, mock_controller, mock_market,
+        redeem, repay, run, transfer, MockDeps,
+    };
+
+    #[test]
+    fn controller_allows_every_action() {
+        let mut deps = mock_market(Some(mock_controller()));
+
+        mint(&mut deps, "alice", 1_000).unwrap();
+        assert_eq!(balance(&deps, "alice"), 1_000);
+
+        // The controller decides alone, so the local collateral factor does not apply
+        borrow(&mut deps, "alice", 800).unwrap();
+        assert_eq!(account_borrow(&deps, "alice"), 800);
+
+        repay(&mut deps, "alice", 300).unwrap();
+        assert_eq!(account_borrow(&deps, "alice"), 500);
+
+        redeem(&mut deps, "alice", 100).unwrap();
+        assert_eq!(balance(&deps, "alice"), 900);
+
+        transfer(&mut deps, "alice", "bob", 400).unwrap();
+        assert_eq!(balance(&deps, "alice"), 500);
+        assert_eq!(balance(&deps, "bob"), 400);
+    }
+
+    #[test]
+    fn controller_denies_mint() {
+        let mut deps = mock_market(Some(mock_controller()));
+        deps.querier.deny("mint");
+
+        assert_generic_err(mint(&mut deps, "alice", 1_000), "The controller rejected the mint: mint is paused");
+        assert_eq!(balance(&deps, "alice"), 0);
+
+        deps.querier.allow("mint");
+        mint(&mut deps, "alice", 1_000).unwrap();
+        assert_eq!(balance(&deps, "alice"), 1_000);
+    }
+
+    #[test]
+    fn controller_denies_redeem() {
+        let mut deps = mock_market(Some(mock_controller()));
+        mint(&mut deps, "alice", 1_000).unwrap();
+        deps.querier.deny("redeem");
+
+        assert_generic_err(redeem(&mut deps, "alice", 100), "The controller rejected the redeem");
+        assert_eq!(balance(&deps, "alice"), 1_000);
+    }
+
+    #[test]
+    fn controller_denies_borrow() {
+        let mut deps = mock_market(Some(mock_controller()));
+        mint(&mut deps, "alice", 1_000).unwrap();
+        deps.querier.deny("borrow");
+
+        assert_generic_err(borrow(&mut deps, "alice", 100), "The controller rejected the borrow");
+        assert_eq!(account_borrow(&deps, "alice"), 0);
+    }
+
+    #[test]
+    fn controller_denies_repay() {
+        let mut deps = mock_market(Some(mock_controller()));
+        mint(&mut deps, "alice", 1_000).unwrap();
+        borrow(&mut deps, "alice", 100).unwrap();
+        deps.querier.deny("repay");
+
+        assert_generic_err(repay(&mut deps, "alice", 100), "The controller rejected the repay");
+        assert_eq!(account_borrow(&deps, "alice"), 100);
+    }
+
+    #[test]
+    fn controller_denies_transfer() {
+        let mut deps = mock_market(Some(mock_controller()));
+        mint(&mut deps, "alice", 1_000).unwrap();
+        deps.querier.deny("transfer");
+
+        assert_generic_err(transfer(&mut deps, "alice", "bob", 100), "The controller rejected the transfer");
+        assert_eq!(balance(&deps, "alice"), 1_000);
+        assert_eq!(balance(&deps, "bob"), 0);
+    }
+
+    fn liquidate(deps: &mut MockDeps, liquidator: &str, borrower: &str, repay_amount: u128) -> StdResult<HandleResponse> {
+        let msg = HandleMsg::LiquidateBorrow {
+            borrower: HumanAddr::from(borrower),
+            repay_amount: Uint128::from(repay_amount),
+        };
+        run(deps, liquidator, repay_amount, msg)
+    }
+
+    #[test]
+    fn controller_decides_liquidations_alone() {
+        let mut deps = mock_market(Some(mock_controller()));
+        mint(&mut deps, "alice", 1_000).unwrap();
+        borrow(&mut deps, "alice", 100).unwrap();
+
+        // Alice has no local shortfall, but the controller may know of borrows in other markets
+        liquidate(&mut deps, "bob", "alice", 50).unwrap();
+        assert_eq!(account_borrow(&deps, "alice"), 50);
+        assert_eq!(balance(&deps, "bob"), 55);
+
+        deps.querier.deny("liquidation");
+        assert_generic_err(
+            liquidate(&mut deps, "bob", "alice", 10),
+            "The controller rejected the liquidation: liquidation is paused",
+        );
+        deps.querier.allow("liquidation");
+        deps.querier.deny("seizure");
+        assert_generic_err(liquidate(&mut deps, "bob", "alice", 10), "The controller rejected the seizure");
+        assert_eq!(account_borrow(&deps, "alice"), 50);
+        assert_eq!(balance(&deps, "alice"), 945);
+    }
+
+    #[test]
+    fn hypothetical_account_liquidity() {
+        let mut deps = mock_market(None);
+        mint(&mut deps, "alice", 1_000).unwrap();
+        let alice = canonical(&deps, "alice");
+        let env = mock_env("alice", &[]);
+
+        // Half of the 1000 cTokens worth 1 each count as collateral
+        let liquidity = get_hypothetical_account_liquidity(&deps, env.clone(), &alice, 0, 0).unwrap();
+        assert_eq!(liquidity, (500, 0));
+        let liquidity = get_hypothetical_account_liquidity(&deps, env.clone(), &alice, 200, 0).unwrap();
+        assert_eq!(liquidity, (400, 0));
+        let liquidity = get_hypothetical_account_liquidity(&deps, env, &alice, 0, 600).unwrap();
+        assert_eq!(liquidity, (0, 100));
+    }
+
+    #[test]
+    fn borrow_without_controller_needs_collateral() {
+        let mut deps = mock_market(None);
+        mint(&mut deps, "alice", 1_000).unwrap();
+
+        assert_generic_err(
+            borrow(&mut deps, "alice", 600),
+            "Insufficient collateral to borrow: borrow_amount: 600, shortfall: 100",
+        );
+        assert_eq!(account_borrow(&deps, "alice"), 0);
+
+        // Borrowing exactly the collateral value leaves no shortfall
+        borrow(&mut deps, "alice", 500).unwrap();
+        assert_eq!(account_borrow(&deps, "alice"), 500);
+    }
+
+    #[test]
+    fn redeem_and_transfer_without_controller_keep_the_borrow_collateralized() {
+        let mut deps = mock_market(None);
+        mint(&mut deps, "alice", 1_000).unwrap();
+        borrow(&mut deps, "alice", 400).unwrap();
+
+        // 202 cTokens are worth 101 of collateral, one more than the liquidity of 100
+        assert_generic_err(redeem(&mut deps, "alice", 202), "Insufficient collateral to redeem");
+        assert_generic_err(transfer(&mut deps, "alice", "bob", 202), "Insufficient collateral to redeem");
+        assert_eq!(balance(&deps, "alice"), 1_000);
+
+        transfer(&mut deps, "alice", "bob", 100).unwrap();
+        redeem(&mut deps, "alice", 100).unwrap();
+        assert_eq!(balance(&deps, "alice"), 800);
+        assert_eq!(balance(&deps, "bob"), 100);
+
+        // Repaying the borrow frees the rest of the collateral
+        repay(&mut deps, "alice", 400).unwrap();
+        redeem(&mut deps, "alice", 800).unwrap();
+        assert_eq!(balance(&deps, "alice"), 0);
+    }
+}
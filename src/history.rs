@@ -0,0 +1,472 @@
+// Per account history of the market.
+// Every account has its own AppendStores of the cToken transactions and lending events it took
+// part in, so a page of its history is read without scanning the other accounts'.
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use cosmwasm_std::{
+    Api, BlockInfo, CanonicalAddr, HumanAddr, ReadonlyStorage, StdResult, Storage, Uint128,
+};
+use cosmwasm_storage::{PrefixedStorage, ReadonlyPrefixedStorage};
+
+use secret_toolkit::storage::{AppendStore, AppendStoreMut};
+
+use crate::state::{may_load, save};
+
+pub const PREFIX_TXS: &[u8] = b"transfers";
+pub const TX_COUNT_KEY: &[u8] = b"txcount";
+pub const PREFIX_LENDING_EVENTS: &[u8] = b"lendingevents";
+pub const LENDING_EVENT_COUNT_KEY: &[u8] = b"lendingeventcount";
+
+/// cToken movement as returned by the TransferHistory query
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub struct Tx {
+    pub id: u64,
+    pub action: TxAction,
+    pub amount: Uint128,
+    pub block_height: u64,
+    pub block_time: u64,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum TxAction {
+    Transfer {
+        from: HumanAddr,
+        sender: HumanAddr,
+        recipient: HumanAddr,
+    },
+    Mint {
+        minter: HumanAddr,
+        recipient: HumanAddr,
+    },
+    Burn {
+        burner: HumanAddr,
+        owner: HumanAddr,
+    },
+}
+
+// Transactions are stored with canonical addresses, which are smaller than human ones
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+enum StoredTxAction {
+    Transfer {
+        from: CanonicalAddr,
+        sender: CanonicalAddr,
+        recipient: CanonicalAddr,
+    },
+    Mint {
+        minter: CanonicalAddr,
+        recipient: CanonicalAddr,
+    },
+    Burn {
+        burner: CanonicalAddr,
+        owner: CanonicalAddr,
+    },
+}
+
+impl StoredTxAction {
+    fn into_humanized<A: Api>(self, api: &A) -> StdResult<TxAction> {
+        let action = match self {
+            StoredTxAction::Transfer { from, sender, recipient } => TxAction::Transfer {
+                from: api.human_address(&from)?,
+                sender: api.human_address(&sender)?,
+                recipient: api.human_address(&recipient)?,
+            },
+            StoredTxAction::Mint { minter, recipient } => TxAction::Mint {
+                minter: api.human_address(&minter)?,
+                recipient: api.human_address(&recipient)?,
+            },
+            StoredTxAction::Burn { burner, owner } => TxAction::Burn {
+                burner: api.human_address(&burner)?,
+                owner: api.human_address(&owner)?,
+            },
+        };
+        Ok(action)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct StoredTx {
+    id: u64,
+    action: StoredTxAction,
+    amount: u128,
+    block_height: u64,
+    block_time: u64,
+}
+
+impl StoredTx {
+    fn into_humanized<A: Api>(self, api: &A) -> StdResult<Tx> {
+        Ok(Tx {
+            id: self.id,
+            action: self.action.into_humanized(api)?,
+            amount: Uint128::from(self.amount),
+            block_height: self.block_height,
+            block_time: self.block_time,
+        })
+    }
+}
+
+/// entry of the loan ledger of an account as returned by the LendingHistory query. Rates and
+/// indices are fixed-point decimals scaled by 10^18, balances are those of the account after the event.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub struct LendingEvent {
+    pub id: u64,
+    pub action: LendingAction,
+    pub underlying_amount: Uint128,
+    pub ctoken_amount: Uint128,
+    pub exchange_rate: Uint128,
+    pub borrow_index: Uint128,
+    pub ctoken_balance: Uint128,
+    pub borrow_balance: Uint128,
+    pub block_height: u64,
+    pub block_time: u64,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum LendingAction {
+    Mint {},
+    Redeem {},
+    Borrow {},
+    Repay {},
+    /// `liquidator` repaid part of the borrow of `borrower` and seized its cTokens
+    Liquidation {
+        liquidator: HumanAddr,
+        borrower: HumanAddr,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub enum StoredLendingAction {
+    Mint,
+    Redeem,
+    Borrow,
+    Repay,
+    Liquidation {
+        liquidator: CanonicalAddr,
+        borrower: CanonicalAddr,
+    },
+}
+
+impl StoredLendingAction {
+    fn into_humanized<A: Api>(self, api: &A) -> StdResult<LendingAction> {
+        let action = match self {
+            StoredLendingAction::Mint => LendingAction::Mint {},
+            StoredLendingAction::Redeem => LendingAction::Redeem {},
+            StoredLendingAction::Borrow => LendingAction::Borrow {},
+            StoredLendingAction::Repay => LendingAction::Repay {},
+            StoredLendingAction::Liquidation { liquidator, borrower } => LendingAction::Liquidation {
+                liquidator: api.human_address(&liquidator)?,
+                borrower: api.human_address(&borrower)?,
+            },
+        };
+        Ok(action)
+    }
+}
+
+/// lending event as it is recorded, the id is assigned when it is stored
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct StoredLendingEvent {
+    pub action: StoredLendingAction,
+    pub underlying_amount: u128,
+    pub ctoken_amount: u128,
+    pub exchange_rate: u128,
+    pub borrow_index: u128,
+    pub ctoken_balance: u128,
+    pub borrow_balance: u128,
+    pub block_height: u64,
+    pub block_time: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct StoredLendingRecord {
+    id: u64,
+    event: StoredLendingEvent,
+}
+
+impl StoredLendingRecord {
+    fn into_humanized<A: Api>(self, api: &A) -> StdResult<LendingEvent> {
+        let event = self.event;
+        Ok(LendingEvent {
+            id: self.id,
+            action: event.action.into_humanized(api)?,
+            underlying_amount: Uint128::from(event.underlying_amount),
+            ctoken_amount: Uint128::from(event.ctoken_amount),
+            exchange_rate: Uint128::from(event.exchange_rate),
+            borrow_index: Uint128::from(event.borrow_index),
+            ctoken_balance: Uint128::from(event.ctoken_balance),
+            borrow_balance: Uint128::from(event.borrow_balance),
+            block_height: event.block_height,
+            block_time: event.block_time,
+        })
+    }
+}
+
+/// Records a transfer of `amount` cTokens from `owner` to `receiver`, executed by `sender`
+pub fn store_transfer<S: Storage>(
+    store: &mut S,
+    owner: &CanonicalAddr,
+    sender: &CanonicalAddr,
+    receiver: &CanonicalAddr,
+    amount: u128,
+    block: &BlockInfo,
+) -> StdResult<()> {
+    let action = StoredTxAction::Transfer {
+        from: owner.clone(),
+        sender: sender.clone(),
+        recipient: receiver.clone(),
+    };
+    let tx = new_tx(store, action, amount, block)?;
+
+    // Every account involved sees the transfer once
+    append_tx(store, &tx, owner)?;
+    if sender != owner {
+        append_tx(store, &tx, sender)?;
+    }
+    if receiver != owner && receiver != sender {
+        append_tx(store, &tx, receiver)?;
+    }
+    Ok(())
+}
+
+/// Records `amount` cTokens minted to `recipient` for the deposit of `minter`
+pub fn store_mint<S: Storage>(
+    store: &mut S,
+    minter: &CanonicalAddr,
+    recipient: &CanonicalAddr,
+    amount: u128,
+    block: &BlockInfo,
+) -> StdResult<()> {
+    let action = StoredTxAction::Mint {
+        minter: minter.clone(),
+        recipient: recipient.clone(),
+    };
+    let tx = new_tx(store, action, amount, block)?;
+
+    append_tx(store, &tx, recipient)?;
+    if recipient != minter {
+        append_tx(store, &tx, minter)?;
+    }
+    Ok(())
+}
+
+/// Records `amount` cTokens of `owner` burnt by `burner`
+pub fn store_burn<S: Storage>(
+    store: &mut S,
+    owner: &CanonicalAddr,
+    burner: &CanonicalAddr,
+    amount: u128,
+    block: &BlockInfo,
+) -> StdResult<()> {
+    let action = StoredTxAction::Burn {
+        burner: burner.clone(),
+        owner: owner.clone(),
+    };
+    let tx = new_tx(store, action, amount, block)?;
+
+    append_tx(store, &tx, owner)?;
+    if burner != owner {
+        append_tx(store, &tx, burner)?;
+    }
+    Ok(())
+}
+
+/// Returns a page of the transfer history of `for_address`, newest first, and its total length
+pub fn get_txs<A: Api, S: ReadonlyStorage>(
+    api: &A,
+    storage: &S,
+    for_address: &CanonicalAddr,
+    page: u32,
+    page_size: u32,
+) -> StdResult<(Vec<Tx>, u64)> {
+    let store = ReadonlyPrefixedStorage::multilevel(&[PREFIX_TXS, for_address.as_slice()], storage);
+
+    // Try to access the storage of txs for the account.
+    // If it doesn't exist yet, return an empty list of transfers.
+    let store = AppendStore::<StoredTx, _>::attach(&store);
+    let store = if let Some(result) = store {
+        result?
+    } else {
+        return Ok((vec![], 0));
+    };
+
+    // Take `page_size` txs starting from the latest tx, potentially skipping `page * page_size`
+    // txs from the start.
+    let tx_iter = store
+        .iter()
+        .rev()
+        .skip((page as usize).saturating_mul(page_size as usize))
+        .take(page_size as usize);
+
+    // The `and_then` here flattens the `StdResult<StdResult<Tx>>` to an `StdResult<Tx>`
+    let txs: StdResult<Vec<Tx>> = tx_iter
+        .map(|tx| tx.map(|tx| tx.into_humanized(api)).and_then(|x| x))
+        .collect();
+    txs.map(|txs| (txs, store.len() as u64))
+}
+
+/// Appends `event` to the lending history of `account`
+pub fn store_lending_event<S: Storage>(
+    store: &mut S,
+    account: &CanonicalAddr,
+    event: StoredLendingEvent,
+) -> StdResult<()> {
+    let record = StoredLendingRecord {
+        id: increment_count(store, LENDING_EVENT_COUNT_KEY)?,
+        event,
+    };
+
+    let mut store = PrefixedStorage::multilevel(&[PREFIX_LENDING_EVENTS, account.as_slice()], store);
+    let mut store = AppendStoreMut::attach_or_create(&mut store)?;
+    store.push(&record)
+}
+
+/// Returns a page of the lending history of `for_address`, newest first, and its total length
+pub fn get_lending_events<A: Api, S: ReadonlyStorage>(
+    api: &A,
+    storage: &S,
+    for_address: &CanonicalAddr,
+    page: u32,
+    page_size: u32,
+) -> StdResult<(Vec<LendingEvent>, u64)> {
+    let store =
+        ReadonlyPrefixedStorage::multilevel(&[PREFIX_LENDING_EVENTS, for_address.as_slice()], storage);
+
+    let store = AppendStore::<StoredLendingRecord, _>::attach(&store);
+    let store = if let Some(result) = store {
+        result?
+    } else {
+        return Ok((vec![], 0));
+    };
+
+    let events: StdResult<Vec<LendingEvent>> = store
+        .iter()
+        .rev()
+        .skip((page as usize).saturating_mul(page_size as usize))
+        .take(page_size as usize)
+        .map(|record| record.and_then(|record| record.into_humanized(api)))
+        .collect();
+    events.map(|events| (events, store.len() as u64))
+}
+
+fn new_tx<S: Storage>(
+    store: &mut S,
+    action: StoredTxAction,
+    amount: u128,
+    block: &BlockInfo,
+) -> StdResult<StoredTx> {
+    Ok(StoredTx {
+        id: increment_count(store, TX_COUNT_KEY)?,
+        action,
+        amount,
+        block_height: block.height,
+        block_time: block.time,
+    })
+}
+
+/// Returns the next id of the counter stored at `key`
+fn increment_count<S: Storage>(store: &mut S, key: &[u8]) -> StdResult<u64> {
+    let id = may_load::<u64, _>(store, key)?.unwrap_or_default() + 1;
+    save(store, key, &id)?;
+    Ok(id)
+}
+
+fn append_tx<S: Storage>(store: &mut S, tx: &StoredTx, for_address: &CanonicalAddr) -> StdResult<()> {
+    let mut store = PrefixedStorage::multilevel(&[PREFIX_TXS, for_address.as_slice()], store);
+    let mut store = AppendStoreMut::attach_or_create(&mut store)?;
+    store.push(tx)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmwasm_std::testing::{mock_env, MockApi, MockStorage};
+
+    fn canonical(api: &MockApi, address: &str) -> CanonicalAddr {
+        api.canonical_address(&HumanAddr::from(address)).unwrap()
+    }
+
+    fn tx_amounts(
+        api: &MockApi,
+        storage: &MockStorage,
+        address: &str,
+        page: u32,
+        page_size: u32,
+    ) -> (Vec<u128>, u64) {
+        let (txs, total) = get_txs(api, storage, &canonical(api, address), page, page_size).unwrap();
+        (txs.iter().map(|tx| tx.amount.u128()).collect(), total)
+    }
+
+    #[test]
+    fn a_transfer_is_listed_once_per_account() {
+        let api = MockApi::new(20);
+        let mut storage = MockStorage::default();
+        let block = mock_env("alice", &[]).block;
+        let alice = canonical(&api, "alice");
+        let bob = canonical(&api, "bob");
+        let carol = canonical(&api, "carol");
+
+        store_transfer(&mut storage, &alice, &bob, &carol, 1, &block).unwrap();
+        store_transfer(&mut storage, &alice, &alice, &alice, 2, &block).unwrap();
+        store_transfer(&mut storage, &alice, &bob, &bob, 3, &block).unwrap();
+        store_transfer(&mut storage, &alice, &alice, &carol, 4, &block).unwrap();
+
+        assert_eq!(tx_amounts(&api, &storage, "alice", 0, 10), (vec![4, 3, 2, 1], 4));
+        assert_eq!(tx_amounts(&api, &storage, "bob", 0, 10), (vec![3, 1], 2));
+        assert_eq!(tx_amounts(&api, &storage, "carol", 0, 10), (vec![4, 1], 2));
+
+        let (txs, _) = get_txs(&api, &storage, &carol, 0, 10).unwrap();
+        assert_eq!(
+            txs[1].action,
+            TxAction::Transfer {
+                from: HumanAddr::from("alice"),
+                sender: HumanAddr::from("bob"),
+                recipient: HumanAddr::from("carol"),
+            }
+        );
+    }
+
+    #[test]
+    fn mints_and_burns_are_listed_once_per_account() {
+        let api = MockApi::new(20);
+        let mut storage = MockStorage::default();
+        let block = mock_env("alice", &[]).block;
+        let (alice, bob) = (canonical(&api, "alice"), canonical(&api, "bob"));
+
+        store_mint(&mut storage, &alice, &alice, 1, &block).unwrap();
+        store_mint(&mut storage, &bob, &alice, 2, &block).unwrap();
+        store_burn(&mut storage, &alice, &alice, 3, &block).unwrap();
+        store_burn(&mut storage, &alice, &bob, 4, &block).unwrap();
+
+        assert_eq!(tx_amounts(&api, &storage, "alice", 0, 10), (vec![4, 3, 2, 1], 4));
+        assert_eq!(tx_amounts(&api, &storage, "bob", 0, 10), (vec![4, 2], 2));
+    }
+
+    #[test]
+    fn history_pages_start_from_the_newest_transaction() {
+        let api = MockApi::new(20);
+        let mut storage = MockStorage::default();
+        let mut block = mock_env("alice", &[]).block;
+        let (alice, bob) = (canonical(&api, "alice"), canonical(&api, "bob"));
+
+        assert_eq!(tx_amounts(&api, &storage, "alice", 0, 10), (vec![], 0));
+
+        for amount in 1..=5 {
+            block.height += 1;
+            store_transfer(&mut storage, &alice, &alice, &bob, amount, &block).unwrap();
+        }
+
+        assert_eq!(tx_amounts(&api, &storage, "alice", 0, 2), (vec![5, 4], 5));
+        assert_eq!(tx_amounts(&api, &storage, "alice", 1, 2), (vec![3, 2], 5));
+        assert_eq!(tx_amounts(&api, &storage, "alice", 2, 2), (vec![1], 5));
+        assert_eq!(tx_amounts(&api, &storage, "alice", 3, 2), (vec![], 5));
+        assert_eq!(tx_amounts(&api, &storage, "alice", 1, 3), (vec![2, 1], 5));
+
+        let (txs, _) = get_txs(&api, &storage, &alice, 0, 1).unwrap();
+        assert_eq!(txs[0].id, 5);
+        assert_eq!(txs[0].block_height, block.height);
+    }
+}
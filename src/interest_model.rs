@@ -1,36 +1,312 @@
-// A mockup interest model
-// TODO: make this in a separate contract and let someone manage this per each collaterized asset
-// use cosmwasm_std::{Uint128};
-// constant that needs to be managed
-pub static  multiplier_per_block: u128 = 23; // 0.000000237823 * 10^8
-pub static  base_rate_per_block: u128 = 0;
-pub static  jump_multiplier_per_block: u128 = 51; // 0.000000518455 * 10^8;
-pub static kink: u128 =  80_000_000; // 0.8 * 10^8
-//pub static config_reserve_factor: u128 = 5_000_000; // 0.05 * 10^8
+// Interest rate models, chosen per market at init and changed through governance.
+// A market can also point at a separate interest model contract shared by several markets.
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
 
-pub fn get_utilization_rate(cash: u128, borrows: u128, reserves: u128) -> u128 {
-    if borrows == (0u128) {
-        return 0;
+use cosmwasm_std::{Querier, StdError, StdResult, Uint128};
+
+use secret_toolkit::utils::Query;
+
+use lending_common::exponential::{Exp, Rounding};
+use lending_common::interest::JumpRate;
+pub use lending_common::interest::{get_utilization_rate, supply_rate_from_borrow_rate};
+
+use crate::contract::BLOCK_SIZE;
+use crate::msg::Contract;
+
+// default jump rate model, as mantissas of `Exp` per block
+const DEFAULT_BASE_RATE: u128 = 0;
+const DEFAULT_MULTIPLIER: u128 = 237_823_000_000; // 0.000000237823 * 10^18
+const DEFAULT_JUMP_MULTIPLIER: u128 = 518_455_000_000; // 0.000000518455 * 10^18
+const DEFAULT_KINK: u128 = 800_000_000_000_000_000; // 0.8 * 10^18
+
+/// average block time of the network, used to derive per second defaults
+pub const SECONDS_PER_BLOCK: u128 = 6;
+pub const SECONDS_PER_DAY: u128 = 86_400;
+pub const DAYS_PER_YEAR: u32 = 365;
+
+/// period rates are expressed in and interest accrues over
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AccrualMode {
+    /// rates are per block and interest accrues with block height
+    Block,
+    /// rates are per second and interest accrues with block time
+    Time,
+}
+
+impl Default for AccrualMode {
+    fn default() -> Self {
+        AccrualMode::Block
+    }
+}
+
+impl AccrualMode {
+    /// Returns the number of accrual periods in a day
+    pub fn periods_per_day(&self) -> u128 {
+        match self {
+            AccrualMode::Block => SECONDS_PER_DAY / SECONDS_PER_BLOCK,
+            AccrualMode::Time => SECONDS_PER_DAY,
+        }
+    }
+}
+
+/// A curve giving the borrow and supply rates per period from the balances of a market.
+/// The querier lets a model be looked up in another contract.
+pub trait InterestRateModel {
+    /// Returns the borrow rate per period
+    fn borrow_rate<Q: Querier>(&self, querier: &Q, cash: u128, borrows: u128, reserves: u128) -> StdResult<Exp>;
+
+    /// Returns the supply rate per period, i.e. the share of the borrow interest paid to suppliers
+    fn supply_rate<Q: Querier>(&self, querier: &Q, cash: u128, borrows: u128, reserves: u128, reserve_factor: Exp) -> StdResult<Exp> {
+        let borrow_rate = self.borrow_rate(querier, cash, borrows, reserves)?;
+        supply_rate_from_borrow_rate(borrow_rate, cash, borrows, reserves, reserve_factor)
+    }
+}
+
+/// queries a market sends to an interest model contract
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum InterestModelQueryMsg {
+    BorrowRate {
+        cash: Uint128,
+        borrows: Uint128,
+        reserves: Uint128,
+    },
+    SupplyRate {
+        cash: Uint128,
+        borrows: Uint128,
+        reserves: Uint128,
+        reserve_factor: Uint128,
+    },
+}
+
+impl Query for InterestModelQueryMsg {
+    const BLOCK_SIZE: usize = BLOCK_SIZE;
+}
+
+/// interest model contract answer to `BorrowRate` and `SupplyRate`, as the mantissa of an `Exp`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RateResponse {
+    pub rate: Uint128,
+}
+
+/// interest rate model of a market, all values are mantissas of `Exp`.
+/// Rates are per block, or per second when the market accrues interest with `AccrualMode::Time`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum InterestModel {
+    /// linear up to the kink utilization, then rising with the steeper jump multiplier
+    JumpRate {
+        base_rate: Uint128,
+        multiplier: Uint128,
+        jump_multiplier: Uint128,
+        kink: Uint128,
+    },
+    /// base_rate + utilization * multiplier
+    Linear {
+        base_rate: Uint128,
+        multiplier: Uint128,
+    },
+    /// the same borrow rate whatever the utilization
+    Fixed {
+        rate: Uint128,
+    },
+    /// rates are queried from an interest model contract
+    External {
+        contract: Contract,
+    },
+}
+
+impl Default for InterestModel {
+    fn default() -> Self {
+        InterestModel::JumpRate {
+            base_rate: Uint128::from(DEFAULT_BASE_RATE),
+            multiplier: Uint128::from(DEFAULT_MULTIPLIER),
+            jump_multiplier: Uint128::from(DEFAULT_JUMP_MULTIPLIER),
+            kink: Uint128::from(DEFAULT_KINK),
+        }
+    }
+}
+
+impl InterestModel {
+    /// Returns the default model with rates expressed per period of `mode`
+    pub fn default_for(mode: AccrualMode) -> Self {
+        match mode {
+            AccrualMode::Block => InterestModel::default(),
+            AccrualMode::Time => InterestModel::JumpRate {
+                base_rate: Uint128::from(DEFAULT_BASE_RATE / SECONDS_PER_BLOCK),
+                multiplier: Uint128::from(DEFAULT_MULTIPLIER / SECONDS_PER_BLOCK),
+                jump_multiplier: Uint128::from(DEFAULT_JUMP_MULTIPLIER / SECONDS_PER_BLOCK),
+                kink: Uint128::from(DEFAULT_KINK),
+            },
+        }
+    }
+
+    /// Returns the highest borrow rate per period of the model, i.e. its rate at full
+    /// utilization, or `None` when the rates come from an external contract
+    pub fn max_borrow_rate(&self) -> StdResult<Option<Exp>> {
+        let rate = match self {
+            InterestModel::JumpRate { base_rate, multiplier, jump_multiplier, kink } => {
+                jump_rate(*base_rate, *multiplier, *jump_multiplier, *kink).borrow_rate(Exp::one())?
+            },
+            InterestModel::Linear { base_rate, multiplier } => {
+                Exp::from(*multiplier).checked_add(Exp::from(*base_rate))?
+            },
+            InterestModel::Fixed { rate } => Exp::from(*rate),
+            InterestModel::External { .. } => return Ok(None),
+        };
+        Ok(Some(rate))
+    }
+
+    /// Returns an error if the parameters do not describe a sensible model
+    pub fn validate(&self) -> StdResult<()> {
+        match self {
+            InterestModel::JumpRate { base_rate, multiplier, jump_multiplier, kink } => {
+                jump_rate(*base_rate, *multiplier, *jump_multiplier, *kink).validate()
+            },
+            InterestModel::Linear { .. } | InterestModel::Fixed { .. } => Ok(()),
+            InterestModel::External { contract } => {
+                if contract.code_hash.is_empty() {
+                    return Err(StdError::generic_err("interest model contract code hash is missing"));
+                }
+                Ok(())
+            },
+        }
+    }
+}
+
+impl InterestRateModel for InterestModel {
+    fn borrow_rate<Q: Querier>(&self, querier: &Q, cash: u128, borrows: u128, reserves: u128) -> StdResult<Exp> {
+        match self {
+            InterestModel::JumpRate { base_rate, multiplier, jump_multiplier, kink } => {
+                let util = get_utilization_rate(cash, borrows, reserves)?;
+                jump_rate(*base_rate, *multiplier, *jump_multiplier, *kink).borrow_rate(util)
+            },
+            InterestModel::Linear { base_rate, multiplier } => {
+                let util = get_utilization_rate(cash, borrows, reserves)?;
+                util.checked_mul(Exp::from(*multiplier), Rounding::Down)?
+                    .checked_add(Exp::from(*base_rate))
+            },
+            InterestModel::Fixed { rate } => Ok(Exp::from(*rate)),
+            InterestModel::External { contract } => {
+                let response: RateResponse = InterestModelQueryMsg::BorrowRate {
+                    cash: Uint128::from(cash),
+                    borrows: Uint128::from(borrows),
+                    reserves: Uint128::from(reserves),
+                }
+                .query(querier, contract.code_hash.clone(), contract.address.clone())?;
+                Ok(Exp::from(response.rate))
+            },
+        }
+    }
+
+    fn supply_rate<Q: Querier>(&self, querier: &Q, cash: u128, borrows: u128, reserves: u128, reserve_factor: Exp) -> StdResult<Exp> {
+        match self {
+            InterestModel::External { contract } => {
+                let response: RateResponse = InterestModelQueryMsg::SupplyRate {
+                    cash: Uint128::from(cash),
+                    borrows: Uint128::from(borrows),
+                    reserves: Uint128::from(reserves),
+                    reserve_factor: Uint128::from(reserve_factor),
+                }
+                .query(querier, contract.code_hash.clone(), contract.address.clone())?;
+                Ok(Exp::from(response.rate))
+            },
+            _ => {
+                let borrow_rate = self.borrow_rate(querier, cash, borrows, reserves)?;
+                supply_rate_from_borrow_rate(borrow_rate, cash, borrows, reserves, reserve_factor)
+            },
+        }
     }
-    borrows/((cash + borrows) - reserves)
 }
 
+/// Returns the curve of the `JumpRate` model with these parameters
+fn jump_rate(base_rate: Uint128, multiplier: Uint128, jump_multiplier: Uint128, kink: Uint128) -> JumpRate {
+    JumpRate {
+        base_rate: Exp::from(base_rate),
+        multiplier: Exp::from(multiplier),
+        jump_multiplier: Exp::from(jump_multiplier),
+        kink: Exp::from(kink),
+    }
+}
+
+/// Returns the borrow rate per period of the market model
+pub fn get_borrow_rate<Q: Querier>(querier: &Q, model: &InterestModel, cash: u128, borrows: u128, reserves: u128) -> StdResult<Exp> {
+    model.borrow_rate(querier, cash, borrows, reserves)
+}
+
+/// Returns the supply rate per period of the market model
+pub fn get_supply_rate<Q: Querier>(querier: &Q, model: &InterestModel, cash: u128, borrows: u128, reserves: u128, reserve_factor: Exp) -> StdResult<Exp> {
+    model.supply_rate(querier, cash, borrows, reserves, reserve_factor)
+}
 
-pub fn get_borrow_rate(cash: u128, borrows: u128, reserves: u128) -> u128 {
-    let util = get_utilization_rate(cash, borrows, reserves);
+/// Returns the annual percentage yield of `rate_per_period`, compounded daily:
+/// (1 + rate_per_period * periods_per_day)^365 - 1
+pub fn annual_percentage_yield(rate_per_period: Exp, mode: AccrualMode) -> StdResult<Exp> {
+    let daily_rate = rate_per_period.mul_scalar(mode.periods_per_day())?;
+    Exp::one()
+        .checked_add(daily_rate)?
+        .checked_pow(DAYS_PER_YEAR)?
+        .checked_sub(Exp::one())
+}
 
-    if util <= kink {
-        return util * multiplier_per_block + base_rate_per_block;
-    } else {
-        let normal_rate = kink * multiplier_per_block + base_rate_per_block;
-        let excess_util = util - kink;
-        return excess_util * jump_multiplier_per_block + normal_rate;
+/// Returns (1 + rate)^periods - 1, the interest factor of `rate` compounded every period.
+/// Uses the first three terms of the binomial expansion, which is accurate for small rates:
+/// periods * rate + C(periods, 2) * rate^2 + C(periods, 3) * rate^3
+pub fn compound_interest_factor(rate: Exp, periods: u128) -> StdResult<Exp> {
+    if periods == 0 || rate.is_zero() {
+        return Ok(Exp::zero());
     }
+
+    let periods_minus_one = periods - 1;
+    let periods_minus_two = periods.saturating_sub(2);
+
+    let rate_pow_two = rate.checked_mul(rate, Rounding::Down)?;
+    let rate_pow_three = rate_pow_two.checked_mul(rate, Rounding::Down)?;
+
+    let first_term = rate.mul_scalar(periods)?;
+    let second_term = rate_pow_two
+        .mul_scalar(periods)?
+        .mul_scalar(periods_minus_one)?
+        .checked_div(Exp::from_integer(2)?, Rounding::Down)?;
+    let third_term = rate_pow_three
+        .mul_scalar(periods)?
+        .mul_scalar(periods_minus_one)?
+        .mul_scalar(periods_minus_two)?
+        .checked_div(Exp::from_integer(6)?, Rounding::Down)?;
+
+    first_term.checked_add(second_term)?.checked_add(third_term)
 }
 
-pub fn get_supply_rate(cash: u128, borrows: u128, reserves: u128, reserve_factor: u128) -> u128 {
-    let one_minus_reserve_factor = 1*10_i32.pow(8) as u128 - reserve_factor;
-    let borrow_rate = get_borrow_rate(cash, borrows, reserves);
-    let rate_to_pool = borrow_rate * one_minus_reserve_factor;
-    get_utilization_rate(cash, borrows, reserves) * rate_to_pool
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lending_common::exponential::EXP_SCALE;
+
+    use crate::testing::{assert_generic_err, mock_interest_model, mock_market_dependencies};
+
+    #[test]
+    fn external_model_rates_come_from_the_contract() {
+        let mut deps = mock_market_dependencies(20, &[]);
+        deps.querier.set_rates(Uint128::from(3_000_000_000u128), Uint128::from(1_000_000_000u128));
+        let model = InterestModel::External { contract: mock_interest_model() };
+
+        let borrow_rate = get_borrow_rate(&deps.querier, &model, 1_000, 500, 0).unwrap();
+        assert_eq!(borrow_rate, Exp::from_mantissa(3_000_000_000));
+
+        // The supply rate is the contract's answer, not one derived from the borrow rate
+        let reserve_factor = Exp::from_mantissa(EXP_SCALE / 10);
+        let supply_rate = get_supply_rate(&deps.querier, &model, 1_000, 500, 0, reserve_factor).unwrap();
+        assert_eq!(supply_rate, Exp::from_mantissa(1_000_000_000));
+    }
+
+    #[test]
+    fn external_model_needs_a_code_hash() {
+        let mut contract = mock_interest_model();
+        contract.code_hash = String::new();
+        let model = InterestModel::External { contract };
+        assert_generic_err(model.validate(), "interest model contract code hash is missing");
+        assert!(InterestModel::External { contract: mock_interest_model() }.validate().is_ok());
+    }
 }
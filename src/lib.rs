@@ -2,9 +2,16 @@ pub mod contract;
 pub mod msg;
 pub mod state;
 mod collateral;
+pub mod controller;
 mod token;
-mod interest_model;
-mod exponential;
+mod receiver;
+pub mod interest_model;
+pub mod viewing_key;
+pub mod permit;
+pub mod history;
+
+#[cfg(test)]
+mod testing;
 
 #[cfg(target_arch = "wasm32")]
 mod wasm {
@@ -1,14 +1,14 @@
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use cosmwasm_std::{HumanAddr, Uint128};
-//use cosmwasm_std::{Binary, CosmosMsg, HumanAddr, Querier, StdResult, Uint128};
-
-//use secret_toolkit::snip20::{register_receive_msg, token_info_query, transfer_msg, TokenInfo};
-
-//use crate::contract::BLOCK_SIZE;
+use cosmwasm_std::{Binary, HumanAddr, StdError, StdResult, Uint128};
 
+use crate::history::{LendingEvent, Tx};
+use crate::interest_model::{AccrualMode, InterestModel};
+use crate::permit::Permit;
+use crate::viewing_key::ViewingKey;
 
+/// Rates, factors, indices and the exchange rate are fixed-point decimals scaled by 10^18
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct InitMsg {
     pub name: String,
@@ -19,20 +19,54 @@ pub struct InitMsg {
     pub reserve_factor: Uint128,
     pub borrow_index: Uint128,
     pub max_borrow_rate: Uint128,
-    pub denom: String
+    pub denom: String,
+    pub close_factor: Uint128,
+    pub liquidation_incentive: Uint128,
+    pub collateral_factor: Uint128,
+    pub controller: Option<Contract>,
+    /// SNIP-20 token lent by the market, or None to lend the native `denom`
+    pub underlying_token: Option<Contract>,
+    /// governance address of the market, defaults to the instantiator
+    pub admin: Option<HumanAddr>,
+    /// interest rate model of the market, defaults to the standard jump rate model
+    pub interest_model: Option<InterestModel>,
+    /// compound interest every period instead of once per accrual, defaults to false
+    pub compound_interest: Option<bool>,
+    /// whether rates are per block or per second, defaults to per block
+    pub accrual_mode: Option<AccrualMode>,
+    /// return responses without any log, defaults to false
+    pub quiet_mode: Option<bool>,
+    /// seed of the generator that creates viewing keys
+    pub prng_seed: Binary,
+}
+
+/// code hash and address of a contract
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Contract {
+    pub address: HumanAddr,
+    pub code_hash: String,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum HandleMsg {
     Mint {},
+    /// burn `tokens` cTokens for the underlying they are worth
     Redeem {
-        redeem_tokens_in: Uint128
+        tokens: Uint128
+    },
+    /// withdraw exactly `amount` of the underlying, burning the cTokens it is worth
+    RedeemUnderlying {
+        amount: Uint128
     },
     Borrow {
         borrow_amount: Uint128
     },
     RepayBorrow {},
+    LiquidateBorrow {
+        borrower: HumanAddr,
+        repay_amount: Uint128,
+    },
     Approve {
         spender: HumanAddr,
         amount: Uint128,
@@ -46,27 +80,283 @@ pub enum HandleMsg {
         recipient: HumanAddr,
         amount: Uint128,
     },
+    /// transfer cTokens and call Receive on the recipient if it registered or a code hash is given
+    Send {
+        recipient: HumanAddr,
+        recipient_code_hash: Option<String>,
+        amount: Uint128,
+        msg: Option<Binary>,
+        padding: Option<String>,
+    },
+    SendFrom {
+        owner: HumanAddr,
+        recipient: HumanAddr,
+        recipient_code_hash: Option<String>,
+        amount: Uint128,
+        msg: Option<Binary>,
+        padding: Option<String>,
+    },
+    /// register the code hash Send calls the sender back with
+    RegisterReceive {
+        code_hash: String,
+        padding: Option<String>,
+    },
+    /// destroy cTokens without redeeming them, their underlying goes to the other holders
+    Burn {
+        amount: Uint128,
+        padding: Option<String>,
+    },
+    BurnFrom {
+        owner: HumanAddr,
+        amount: Uint128,
+        padding: Option<String>,
+    },
+    IncreaseAllowance {
+        spender: HumanAddr,
+        amount: Uint128,
+        expiration: Option<u64>,
+        padding: Option<String>,
+    },
+    DecreaseAllowance {
+        spender: HumanAddr,
+        amount: Uint128,
+        expiration: Option<u64>,
+        padding: Option<String>,
+    },
+    /// admin only: drop every log of the responses, even the `action` ones
+    SetQuietMode {
+        quiet_mode: bool,
+        padding: Option<String>,
+    },
+    /// admin only: stop the market, or everything but redeems and repayments
+    SetContractStatus {
+        level: ContractStatusLevel,
+        padding: Option<String>,
+    },
+    /// propose a new admin, who becomes admin once they accept
+    SetPendingAdmin {
+        address: HumanAddr,
+    },
+    /// accept the admin role proposed to the sender
+    AcceptAdmin {},
+    /// admin only: change the risk parameters of the market, unset fields are left unchanged
+    UpdateMarketParams {
+        reserve_factor: Option<Uint128>,
+        max_borrow_rate: Option<Uint128>,
+        interest_model: Option<InterestModel>,
+    },
+    /// generate a viewing key for the sender from `entropy`
+    CreateViewingKey {
+        entropy: String,
+        padding: Option<String>,
+    },
+    /// set the viewing key of the sender
+    SetViewingKey {
+        key: String,
+        padding: Option<String>,
+    },
+    /// revoke the query permits of the sender named `permit_name`
+    RevokePermit {
+        permit_name: String,
+        padding: Option<String>,
+    },
+    /// SNIP-20 receiver interface, called by the underlying token
+    Receive {
+        sender: HumanAddr,
+        from: HumanAddr,
+        amount: Uint128,
+        msg: Option<Binary>,
+    },
+}
+
+/// messages attached to a Send of the underlying SNIP-20 token to the market
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ReceiveMsg {
+    Mint {},
+    RepayBorrow {},
+    LiquidateBorrow {
+        borrower: HumanAddr,
+        repay_amount: Uint128,
+    },
 }
 
 #[derive(Serialize, Deserialize, Debug, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum HandleAnswer {
-
+    Mint {
+        status: ResponseStatus,
+        minted_tokens: Uint128,
+        /// underlying per cToken the tokens were minted at, scaled by 10^18
+        exchange_rate: Uint128,
+    },
+    Redeem {
+        status: ResponseStatus,
+        redeem_tokens: Uint128,
+        redeem_amount: Uint128,
+    },
+    Borrow {
+        status: ResponseStatus,
+        account_borrow: Uint128,
+    },
+    Repay {
+        status: ResponseStatus,
+        repaid: Uint128,
+        remaining: Uint128,
+    },
+    LiquidateBorrow {
+        status: ResponseStatus,
+        repaid: Uint128,
+        seized_tokens: Uint128,
+        remaining: Uint128,
+    },
+    Transfer {
+        status: ResponseStatus,
+    },
+    TransferFrom {
+        status: ResponseStatus,
+    },
+    Approve {
+        status: ResponseStatus,
+    },
+    Send {
+        status: ResponseStatus,
+    },
+    SendFrom {
+        status: ResponseStatus,
+    },
+    RegisterReceive {
+        status: ResponseStatus,
+    },
+    Burn {
+        status: ResponseStatus,
+    },
+    BurnFrom {
+        status: ResponseStatus,
+    },
+    IncreaseAllowance {
+        spender: HumanAddr,
+        owner: HumanAddr,
+        allowance: Uint128,
+    },
+    DecreaseAllowance {
+        spender: HumanAddr,
+        owner: HumanAddr,
+        allowance: Uint128,
+    },
+    SetContractStatus {
+        status: ResponseStatus,
+    },
+    SetQuietMode {
+        status: ResponseStatus,
+    },
+    SetPendingAdmin {
+        status: ResponseStatus,
+    },
+    AcceptAdmin {
+        status: ResponseStatus,
+    },
+    UpdateMarketParams {
+        status: ResponseStatus,
+    },
+    CreateViewingKey {
+        key: ViewingKey,
+    },
+    SetViewingKey {
+        status: ResponseStatus,
+    },
+    RevokePermit {
+        status: ResponseStatus,
+    },
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum QueryMsg {
     Config {},
+    TokenInfo {},
+    ContractStatus {},
     Balance {
         address: HumanAddr,
+        key: String,
+    },
+    Allowance {
+        owner: HumanAddr,
+        spender: HumanAddr,
+        key: String,
+    },
+    /// current borrow balance of `address`, including interest up to the last accrual
+    BorrowBalance {
+        address: HumanAddr,
+        key: String,
+    },
+    /// cToken transfers, mints and burns of `address`, newest first
+    TransferHistory {
+        address: HumanAddr,
+        key: String,
+        page: Option<u32>,
+        page_size: u32,
+    },
+    /// mints, redeems, borrows, repayments and liquidations of `address`, newest first
+    LendingHistory {
+        address: HumanAddr,
+        key: String,
+        page: Option<u32>,
+        page_size: u32,
     },
+    /// current utilization, rates and yields of the market
+    Rates {},
+    /// balances and indices of the market, projected to `at_height` if given
+    MarketState {
+        at_height: Option<u64>,
+    },
+    /// run `query` for the account that signed `permit` instead of checking a viewing key
+    WithPermit {
+        permit: Permit,
+        query: QueryWithPermit,
+    },
+}
+
+/// queries that can be authenticated with a permit, they apply to the signer of the permit
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryWithPermit {
+    Balance {},
     Allowance {
         owner: HumanAddr,
         spender: HumanAddr,
     },
+    BorrowBalance {},
+    TransferHistory {
+        page: Option<u32>,
+        page_size: u32,
+    },
+    LendingHistory {
+        page: Option<u32>,
+        page_size: u32,
+    },
 }
 
+impl QueryMsg {
+    /// Returns the addresses allowed to run the query with the given key, for the queries that
+    /// need a viewing key
+    pub fn get_validation_params(&self) -> StdResult<(Vec<&HumanAddr>, ViewingKey)> {
+        match self {
+            Self::Balance { address, key } => Ok((vec![address], ViewingKey(key.clone()))),
+            Self::Allowance { owner, spender, key } => {
+                Ok((vec![owner, spender], ViewingKey(key.clone())))
+            },
+            Self::BorrowBalance { address, key } => Ok((vec![address], ViewingKey(key.clone()))),
+            Self::TransferHistory { address, key, .. } => {
+                Ok((vec![address], ViewingKey(key.clone())))
+            },
+            Self::LendingHistory { address, key, .. } => {
+                Ok((vec![address], ViewingKey(key.clone())))
+            },
+            _ => Err(StdError::generic_err("This query does not require a viewing key")),
+        }
+    }
+}
 
 /// responses to queries
 #[derive(Serialize, Deserialize, Debug, JsonSchema)]
@@ -81,20 +371,97 @@ pub enum QueryAnswer {
         intital_exchange_rate: Uint128,
         reserve_factor: Uint128,
         borrow_index: Uint128,
-        denom: String
+        denom: String,
+        close_factor: Uint128,
+        liquidation_incentive: Uint128,
+        collateral_factor: Uint128,
+        controller: Option<Contract>,
+        underlying_token: Option<Contract>,
+        admin: HumanAddr,
+        pending_admin: Option<HumanAddr>,
+        max_borrow_rate: Uint128,
+        interest_model: InterestModel,
+        compound_interest: bool,
+        accrual_mode: AccrualMode,
+        quiet_mode: bool,
+    },
+    /// Rates query response, all values are fixed-point decimals scaled by 10^18.
+    /// Rates are per block, or per second when the market accrues interest with time.
+    RatesResponse {
+        utilization_rate: Uint128,
+        borrow_rate: Uint128,
+        supply_rate: Uint128,
+        borrow_apy: Uint128,
+        supply_apy: Uint128,
+        accrual_mode: AccrualMode,
+    },
+    /// MarketState query response, indices and the exchange rate are fixed-point decimals scaled by 10^18
+    MarketStateResponse {
+        cash: Uint128,
+        total_borrows: Uint128,
+        total_reserves: Uint128,
+        total_supply: Uint128,
+        borrow_index: Uint128,
+        exchange_rate: Uint128,
+        accrual_block_number: u64,
+        accrual_time: u64,
     },
     /// Balance query response
     BalanceResponse {
         balance: Uint128,
     },
+    /// TokenInfo query response
+    TokenInfo {
+        name: String,
+        symbol: String,
+        decimals: u8,
+        total_supply: Option<Uint128>,
+    },
+    /// ContractStatus query response
+    ContractStatus {
+        status: ContractStatusLevel,
+    },
     /// Allowance query response
     AllowanceResponse {
+        spender: HumanAddr,
+        owner: HumanAddr,
         allowance: Uint128,
+        expiration: Option<u64>,
+    },
+    /// TransferHistory query response
+    TransferHistory {
+        txs: Vec<Tx>,
+        total: Option<u64>,
+    },
+    /// LendingHistory query response
+    LendingHistory {
+        events: Vec<LendingEvent>,
+        total: Option<u64>,
+    },
+    /// BorrowBalance query response
+    BorrowBalanceResponse {
+        borrow_balance: Uint128,
+    },
+    /// returned instead of the requested data when the viewing key is wrong
+    ViewingKeyError {
+        msg: String,
     },
 }
 
+/// which handle messages the market accepts
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ContractStatusLevel {
+    NormalRun,
+    /// only redeems, repayments and account settings are accepted
+    StopAllButRedeems,
+    /// only the admin can change the status back
+    StopAll,
+}
+
 /// success or failure response
-#[derive(Serialize, Deserialize, Debug, JsonSchema)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
 pub enum ResponseStatus {
     Success,
     Failure,
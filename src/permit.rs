@@ -0,0 +1,343 @@
+// SNIP-24 query permits
+// A permit is an offline signature of the wallet that lets anyone holding it run queries on its
+// behalf, so reading a position does not require a transaction to set a viewing key.
+use ripemd160::{Digest, Ripemd160};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use cosmwasm_std::{
+    to_binary, Api, Binary, CanonicalAddr, Extern, HumanAddr, Querier, StdError, StdResult,
+    Storage, Uint128,
+};
+use cosmwasm_storage::{PrefixedStorage, ReadonlyPrefixedStorage};
+
+use secret_toolkit::crypto::secp256k1::{PublicKey, Signature};
+use secret_toolkit::crypto::sha_256;
+
+pub const REVOKED_PERMITS_PREFIX: &[u8] = b"revokedpermits";
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct Permit {
+    pub params: PermitParams,
+    pub signature: PermitSignature,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct PermitParams {
+    /// contracts the permit can be used with
+    pub allowed_tokens: Vec<HumanAddr>,
+    /// name the permit can be revoked by
+    pub permit_name: String,
+    pub chain_id: String,
+    pub permissions: Vec<Permission>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct PermitSignature {
+    pub pub_key: PubKey,
+    pub signature: Binary,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct PubKey {
+    /// ignored, only secp256k1 keys are supported
+    pub r#type: String,
+    /// compressed secp256k1 public key
+    pub value: Binary,
+}
+
+/// what the holder of a permit is allowed to query
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Permission {
+    /// allowances granted by or to the account
+    Allowance,
+    /// token and borrow balances of the account
+    Balance,
+    /// history of the account
+    History,
+    /// everything the account can query
+    Owner,
+}
+
+impl Permit {
+    pub fn check_token(&self, token: &HumanAddr) -> bool {
+        self.params.allowed_tokens.contains(token)
+    }
+
+    pub fn check_permission(&self, permission: &Permission) -> bool {
+        self.params.permissions.contains(permission)
+            || self.params.permissions.contains(&Permission::Owner)
+    }
+}
+
+/// Returns the address that signed `permit` if it is valid for `current_token_address` and was not
+/// revoked
+pub fn validate<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    permit: &Permit,
+    current_token_address: &HumanAddr,
+) -> StdResult<HumanAddr> {
+    if !permit.check_token(current_token_address) {
+        return Err(StdError::generic_err(format!(
+            "Permit doesn't apply to token {:?}, allowed tokens: {:?}",
+            current_token_address, permit.params.allowed_tokens)
+        )
+        );
+    }
+
+    // The account is derived from the public key the same way the chain does it
+    let pubkey = &permit.signature.pub_key.value;
+    let account = deps.api.human_address(&pubkey_to_account(pubkey))?;
+
+    if is_permit_revoked(&deps.storage, &account, &permit.params.permit_name) {
+        return Err(StdError::generic_err(format!(
+            "Permit {:?} was revoked by account {:?}",
+            permit.params.permit_name, account)
+        )
+        );
+    }
+
+    let signed_bytes = to_binary(&SignedPermit::from_params(&permit.params))?;
+    let signed_bytes_hash = sha_256(signed_bytes.as_slice());
+
+    let public_key = PublicKey::parse(pubkey.as_slice())
+        .map_err(|err| StdError::generic_err(format!("Invalid permit public key: {:?}", err)))?;
+    let signature = Signature::parse_slice(permit.signature.signature.as_slice())
+        .map_err(|err| StdError::generic_err(format!("Invalid permit signature: {:?}", err)))?;
+
+    if !public_key.verify(&signed_bytes_hash, signature) {
+        return Err(StdError::generic_err(
+            "Failed to verify signatures for the given permit",
+        ));
+    }
+
+    Ok(account)
+}
+
+pub fn revoke_permit<S: Storage>(store: &mut S, account: &HumanAddr, permit_name: &str) {
+    let mut revoked_store = PrefixedStorage::new(REVOKED_PERMITS_PREFIX, store);
+    revoked_store.set(&revoked_permit_key(account, permit_name), &[])
+}
+
+pub fn is_permit_revoked<S: Storage>(store: &S, account: &HumanAddr, permit_name: &str) -> bool {
+    let revoked_store = ReadonlyPrefixedStorage::new(REVOKED_PERMITS_PREFIX, store);
+    revoked_store.get(&revoked_permit_key(account, permit_name)).is_some()
+}
+
+fn revoked_permit_key(account: &HumanAddr, permit_name: &str) -> Vec<u8> {
+    [account.as_str().as_bytes(), permit_name.as_bytes()].concat()
+}
+
+/// Returns the raw address of a compressed secp256k1 public key: ripemd160(sha256(pubkey))
+fn pubkey_to_account(pubkey: &Binary) -> CanonicalAddr {
+    let mut hasher = Ripemd160::new();
+    hasher.update(sha_256(pubkey.as_slice()));
+    CanonicalAddr(Binary(hasher.finalize().to_vec()))
+}
+
+// The document the wallet signs: an Amino sign doc holding a single `query_permit` message.
+// Fields are declared in alphabetical order so their JSON encoding is the canonical one.
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+struct SignedPermit {
+    account_number: Uint128,
+    chain_id: String,
+    fee: Fee,
+    memo: String,
+    msgs: Vec<PermitMsg>,
+    sequence: Uint128,
+}
+
+impl SignedPermit {
+    fn from_params(params: &PermitParams) -> Self {
+        Self {
+            account_number: Uint128::from(0u128),
+            chain_id: params.chain_id.clone(),
+            fee: Fee::new(),
+            memo: String::new(),
+            msgs: vec![PermitMsg::from_content(PermitContent::from_params(params))],
+            sequence: Uint128::from(0u128),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+struct Fee {
+    amount: Vec<FeeCoin>,
+    gas: Uint128,
+}
+
+impl Fee {
+    fn new() -> Self {
+        Self {
+            amount: vec![FeeCoin {
+                amount: Uint128::from(0u128),
+                denom: "uscrt".to_string(),
+            }],
+            gas: Uint128::from(1u128),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+struct FeeCoin {
+    amount: Uint128,
+    denom: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+struct PermitMsg {
+    r#type: String,
+    value: PermitContent,
+}
+
+impl PermitMsg {
+    fn from_content(content: PermitContent) -> Self {
+        Self {
+            r#type: "query_permit".to_string(),
+            value: content,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+struct PermitContent {
+    allowed_tokens: Vec<HumanAddr>,
+    permissions: Vec<Permission>,
+    permit_name: String,
+}
+
+impl PermitContent {
+    fn from_params(params: &PermitParams) -> Self {
+        Self {
+            allowed_tokens: params.allowed_tokens.clone(),
+            permissions: params.permissions.clone(),
+            permit_name: params.permit_name.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmwasm_std::from_binary;
+    use cosmwasm_std::testing::MOCK_CONTRACT_ADDR;
+
+    use crate::contract::query;
+    use crate::msg::{HandleMsg, QueryAnswer, QueryMsg, QueryWithPermit};
+    use crate::testing::{assert_generic_err, mint, mock_market, run, MockDeps};
+
+    // Permits signed offline for chain "secret-4" with the secp256k1 private key 4182. Both grant
+    // the "balance" permission under the name "market", for MOCK_CONTRACT_ADDR and
+    // "other_market" respectively.
+    const PUB_KEY: &str = "Ag3CTL8GFWAVcCm2gQpdo6NOyGGmQSMhjex+lL4DxYHx";
+    const SIGNATURE: &str = "L2WCuZfxCxHbJjEwtlJUqoia2Kpijl3YgZJQQGxBcnI0XRKpUeuMl3EV+5IRmYJQl3eatSxqb0n3a2fkYhz9nA==";
+    const OTHER_MARKET_SIGNATURE: &str =
+        "DOqx66DM2HkEHafrtH+F9Qm4ee/j5TcixLgA+EKYDIQ9Ah/s2JrYGpNTqQA76MgnTthUgSnVeEmEv92xWeCQ9g==";
+
+    fn permit(allowed_token: &str, signature: &str) -> Permit {
+        Permit {
+            params: PermitParams {
+                allowed_tokens: vec![HumanAddr::from(allowed_token)],
+                permit_name: "market".to_string(),
+                chain_id: "secret-4".to_string(),
+                permissions: vec![Permission::Balance],
+            },
+            signature: PermitSignature {
+                pub_key: PubKey {
+                    r#type: "tendermint/PubKeySecp256k1".to_string(),
+                    value: Binary::from_base64(PUB_KEY).unwrap(),
+                },
+                signature: Binary::from_base64(signature).unwrap(),
+            },
+        }
+    }
+
+    /// Returns the address of the account that signed the permits
+    fn signer(deps: &MockDeps) -> HumanAddr {
+        let pubkey = Binary::from_base64(PUB_KEY).unwrap();
+        deps.api.human_address(&pubkey_to_account(&pubkey)).unwrap()
+    }
+
+    fn query_with_permit(deps: &MockDeps, permit: Permit, query_msg: QueryWithPermit) -> StdResult<QueryAnswer> {
+        query(deps, QueryMsg::WithPermit { permit, query: query_msg }).and_then(|answer| from_binary(&answer))
+    }
+
+    #[test]
+    fn a_signed_permit_queries_for_its_signer() {
+        let mut deps = mock_market(None);
+        let account = signer(&deps);
+        mint(&mut deps, account.as_str(), 1_000).unwrap();
+
+        let contract = HumanAddr::from(MOCK_CONTRACT_ADDR);
+        assert_eq!(validate(&deps, &permit(MOCK_CONTRACT_ADDR, SIGNATURE), &contract).unwrap(), account);
+        match query_with_permit(&deps, permit(MOCK_CONTRACT_ADDR, SIGNATURE), QueryWithPermit::Balance {}) {
+            Ok(QueryAnswer::BalanceResponse { balance }) => assert_eq!(balance, Uint128::from(1_000u128)),
+            other => panic!("unexpected answer: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn a_permit_for_another_token_is_rejected() {
+        let deps = mock_market(None);
+        assert_generic_err(
+            query_with_permit(&deps, permit("other_market", OTHER_MARKET_SIGNATURE), QueryWithPermit::Balance {}),
+            "Permit doesn't apply to token",
+        );
+    }
+
+    #[test]
+    fn a_permit_without_the_permission_is_rejected() {
+        let deps = mock_market(None);
+        let history = QueryWithPermit::LendingHistory { page: None, page_size: 10 };
+        assert_generic_err(
+            query_with_permit(&deps, permit(MOCK_CONTRACT_ADDR, SIGNATURE), history),
+            "No permission to query, got permissions [Balance]",
+        );
+    }
+
+    #[test]
+    fn a_revoked_permit_is_rejected() {
+        let mut deps = mock_market(None);
+        let account = signer(&deps);
+
+        // Only the signer can revoke its permits
+        let revoke = HandleMsg::RevokePermit { permit_name: "market".to_string(), padding: None };
+        run(&mut deps, "bob", 0, revoke.clone()).unwrap();
+        query_with_permit(&deps, permit(MOCK_CONTRACT_ADDR, SIGNATURE), QueryWithPermit::Balance {}).unwrap();
+
+        run(&mut deps, account.as_str(), 0, revoke).unwrap();
+        assert_generic_err(
+            query_with_permit(&deps, permit(MOCK_CONTRACT_ADDR, SIGNATURE), QueryWithPermit::Balance {}),
+            "Permit \"market\" was revoked by account",
+        );
+    }
+
+    #[test]
+    fn a_permit_with_a_bad_signature_is_rejected() {
+        let deps = mock_market(None);
+        let failed = "Failed to verify signatures for the given permit";
+
+        // A valid signature of other params
+        let contract = HumanAddr::from(MOCK_CONTRACT_ADDR);
+        assert_generic_err(validate(&deps, &permit(MOCK_CONTRACT_ADDR, OTHER_MARKET_SIGNATURE), &contract), failed);
+
+        let mut renamed = permit(MOCK_CONTRACT_ADDR, SIGNATURE);
+        renamed.params.permit_name = "renamed".to_string();
+        assert_generic_err(validate(&deps, &renamed, &contract), failed);
+
+        let mut escalated = permit(MOCK_CONTRACT_ADDR, SIGNATURE);
+        escalated.params.permissions = vec![Permission::Owner];
+        assert_generic_err(query_with_permit(&deps, escalated, QueryWithPermit::Balance {}), failed);
+    }
+}
@@ -0,0 +1,62 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use cosmwasm_std::{to_binary, Binary, CosmosMsg, HumanAddr, StdResult, Uint128, WasmMsg};
+
+use secret_toolkit::utils::space_pad;
+
+use crate::contract::BLOCK_SIZE;
+
+/// callback sent to a contract that receives cTokens through Send or SendFrom
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct Snip20ReceiveMsg {
+    /// address that executed the send
+    pub sender: HumanAddr,
+    /// owner of the cTokens that were sent
+    pub from: HumanAddr,
+    pub amount: Uint128,
+    pub msg: Option<Binary>,
+}
+
+impl Snip20ReceiveMsg {
+    pub fn new(sender: HumanAddr, from: HumanAddr, amount: Uint128, msg: Option<Binary>) -> Self {
+        Self {
+            sender,
+            from,
+            amount,
+            msg,
+        }
+    }
+
+    /// serializes the message, padded to `BLOCK_SIZE` like the rest of the contract's output
+    pub fn into_binary(self) -> StdResult<Binary> {
+        let msg = ReceiverHandleMsg::Receive(self);
+        let mut data = to_binary(&msg)?;
+        space_pad(&mut data.0, BLOCK_SIZE);
+        Ok(data)
+    }
+
+    /// creates a cosmos_msg sending this struct to the named contract
+    pub fn into_cosmos_msg(
+        self,
+        callback_code_hash: String,
+        contract_addr: HumanAddr,
+    ) -> StdResult<CosmosMsg> {
+        let msg = self.into_binary()?;
+        let execute = WasmMsg::Execute {
+            msg,
+            callback_code_hash,
+            contract_addr,
+            send: vec![],
+        };
+        Ok(execute.into())
+    }
+}
+
+// This is just a helper to properly serialize the above message
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+enum ReceiverHandleMsg {
+    Receive(Snip20ReceiveMsg),
+}
@@ -1,13 +1,15 @@
-use std::{any::type_name};
 use std::convert::TryFrom;
 
-use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde::{Deserialize, Serialize};
 
-use cosmwasm_std::{CanonicalAddr, ReadonlyStorage, StdError, StdResult, Storage};
+use cosmwasm_std::{BlockInfo, CanonicalAddr, HumanAddr, ReadonlyStorage, StdError, StdResult, Storage};
 use cosmwasm_storage::{ReadonlyPrefixedStorage, PrefixedStorage, Bucket, ReadonlyBucket};
 
-use secret_toolkit::serialization::{Bincode2, Serde};
-//use secret_toolkit::storage::{AppendStore, AppendStoreMut, TypedStore, TypedStoreMut};
+pub use lending_common::storage::{load, may_load, remove, save};
+
+use crate::interest_model::{AccrualMode, InterestModel};
+use crate::msg::{Contract, ContractStatusLevel};
+use crate::viewing_key::ViewingKey;
 
 /// storage key for contract state
 pub const CONFIG_KEY: &[u8] = b"config";
@@ -15,6 +17,9 @@ pub const STATE_KEY: &[u8] = b"state";
 pub const ALLOWANCE_PREFIX: &[u8] = b"allowance";
 pub const BALANCE_PREFIX: &[u8] = b"balance";
 pub const BORROW_PREFIX: &[u8] = b"borrow";
+pub const PRNG_SEED_KEY: &[u8] = b"prngseed";
+pub const VIEWING_KEY_PREFIX: &[u8] = b"viewingkey";
+pub const RECEIVER_HASH_PREFIX: &[u8] = b"receivers";
 
 /// Config struct
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
@@ -28,13 +33,29 @@ pub struct Config {
     pub borrow_index: u128,
     pub max_borrow_rate: u128,
     pub denom: String,
+    pub close_factor: u128,
+    pub liquidation_incentive: u128,
+    pub collateral_factor: u128,
+    pub controller: Option<Contract>,
+    pub underlying_token: Option<Contract>,
+    pub admin: HumanAddr,
+    pub pending_admin: Option<HumanAddr>,
+    pub interest_model: InterestModel,
+    pub compound_interest: bool,
+    pub accrual_mode: AccrualMode,
+    /// address of the market itself, which query permits must be issued for
+    pub contract_address: HumanAddr,
+    pub contract_status: ContractStatusLevel,
+    /// responses carry no log at all
+    pub quiet_mode: bool,
 }
 
 /// state of the auction
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct State {
     pub cash: u128,
     pub block_number: u64,
+    pub last_accrual_time: u64,
     pub total_reserves: u128,
     pub total_borrows: u128,
     pub exchange_rate: u128,
@@ -43,66 +64,29 @@ pub struct State {
     pub borrow_index: u128,
 }
 
+/// cTokens a spender may move on behalf of an owner
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct Allowance {
+    pub amount: u128,
+    /// block time after which the allowance can no longer be used
+    pub expiration: Option<u64>,
+}
+
+impl Allowance {
+    pub fn is_expired_at(&self, block: &BlockInfo) -> bool {
+        match self.expiration {
+            Some(time) => block.time >= time,
+            None => false,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct BorrowSnapshot {
     pub principal: u128,
     pub interest_index: u128
 }
 
-/// Returns StdResult<()> resulting from saving an item to storage
-///
-/// # Arguments
-///
-/// * `storage` - a mutable reference to the storage this item should go to
-/// * `key` - a byte slice representing the key to access the stored item
-/// * `value` - a reference to the item to store
-pub fn save<T: Serialize, S: Storage>(storage: &mut S, key: &[u8], value: &T) -> StdResult<()> {
-    storage.set(key, &Bincode2::serialize(value)?);
-    Ok(())
-}
-
-/// Removes an item from storage
-///
-/// # Arguments
-///
-/// * `storage` - a mutable reference to the storage this item is in
-/// * `key` - a byte slice representing the key that accesses the stored item
-pub fn remove<S: Storage>(storage: &mut S, key: &[u8]) {
-    storage.remove(key);
-}
-
-/// Returns StdResult<T> from retrieving the item with the specified key.  Returns a
-/// StdError::NotFound if there is no item with that key
-///
-/// # Arguments
-///
-/// * `storage` - a reference to the storage this item is in
-/// * `key` - a byte slice representing the key that accesses the stored item
-pub fn load<T: DeserializeOwned, S: ReadonlyStorage>(storage: &S, key: &[u8]) -> StdResult<T> {
-    Bincode2::deserialize(
-        &storage
-            .get(key)
-            .ok_or_else(|| StdError::not_found(type_name::<T>()))?,
-    )
-}
-
-/// Returns StdResult<Option<T>> from retrieving the item with the specified key.
-/// Returns Ok(None) if there is no item with that key
-///
-/// # Arguments
-///
-/// * `storage` - a reference to the storage this item is in
-/// * `key` - a byte slice representing the key that accesses the stored item
-pub fn may_load<T: DeserializeOwned, S: ReadonlyStorage>(
-    storage: &S,
-    key: &[u8],
-) -> StdResult<Option<T>> {
-    match storage.get(key) {
-        Some(value) => Bincode2::deserialize(&value).map(Some),
-        None => Ok(None),
-    }
-}
-
 /// Config singleton initialization
 /*
 pub fn config<S: Storage>(storage: &mut S) -> Singleton<S, Config> {
@@ -130,9 +114,10 @@ pub fn set_state<S: Storage>(storage: &mut S, state: &State) -> StdResult<()> {
     save(storage, STATE_KEY, state)
 }
 
+/// Get the cToken balance of an account, an account that never held cTokens has none
 pub fn get_balance<S: Storage>(store: &S, owner: &CanonicalAddr) -> StdResult<u128> {
     let balance_store = ReadonlyPrefixedStorage::new(BALANCE_PREFIX, store);
-    load(&balance_store, owner.as_slice())
+    Ok(may_load(&balance_store, owner.as_slice())?.unwrap_or_default())
 }
 
 pub fn set_balance<S: Storage>(store: &mut S, owner: &CanonicalAddr, balance: u128) -> StdResult<()> {
@@ -140,14 +125,15 @@ pub fn set_balance<S: Storage>(store: &mut S, owner: &CanonicalAddr, balance: u1
     save(&mut balance_store, owner.as_slice(), &balance)
 }
 
+/// Get allowance from address, an allowance that was never set is empty
 pub fn get_allowance<S: Storage>(
     store: &S,
     owner: &CanonicalAddr,
     spender: &CanonicalAddr
-) -> StdResult<u128> {
+) -> StdResult<Allowance> {
     let owner_store =
         ReadonlyPrefixedStorage::multilevel(&[ALLOWANCE_PREFIX, owner.as_slice()], store);
-    load(&owner_store, spender.as_slice())
+    Ok(may_load(&owner_store, spender.as_slice())?.unwrap_or_default())
 }
 
 
@@ -156,11 +142,11 @@ pub fn set_allowance<S: Storage>(
     store: &mut S,
     owner: &CanonicalAddr,
     spender: &CanonicalAddr,
-    amount: u128,
+    allowance: &Allowance,
 ) -> StdResult<()> {
     let mut owner_store =
         PrefixedStorage::multilevel(&[ALLOWANCE_PREFIX, owner.as_slice()], store);
-    save(&mut owner_store, spender.as_slice(), &amount)
+    save(&mut owner_store, spender.as_slice(), allowance)
 }
 
 pub fn get_borrow_balance<S: Storage>(store: &S, owner: &CanonicalAddr) -> Option<BorrowSnapshot> {
@@ -184,6 +170,42 @@ pub fn set_borrow_balance<S: Storage>(
     }
 }
 
+/// Get the hash of the seed viewing keys are generated from
+pub fn get_prng_seed<S: Storage>(store: &S) -> StdResult<Vec<u8>> {
+    load(store, PRNG_SEED_KEY)
+}
+
+/// Set the hash of the seed viewing keys are generated from
+pub fn set_prng_seed<S: Storage>(store: &mut S, prng_seed: &[u8]) -> StdResult<()> {
+    save(store, PRNG_SEED_KEY, &prng_seed.to_vec())
+}
+
+/// Returns the hash of the viewing key of `owner`, if one was set
+pub fn read_viewing_key<S: Storage>(store: &S, owner: &CanonicalAddr) -> Option<Vec<u8>> {
+    let key_store = ReadonlyPrefixedStorage::new(VIEWING_KEY_PREFIX, store);
+    key_store.get(owner.as_slice())
+}
+
+/// Stores the hash of the viewing key of `owner`, the key itself is never saved
+pub fn write_viewing_key<S: Storage>(store: &mut S, owner: &CanonicalAddr, key: &ViewingKey) {
+    let mut key_store = PrefixedStorage::new(VIEWING_KEY_PREFIX, store);
+    key_store.set(owner.as_slice(), &key.to_hashed());
+}
+
+/// Returns the code hash `account` registered to receive Send callbacks with, if any
+pub fn get_receiver_hash<S: Storage>(store: &S, account: &HumanAddr) -> Option<StdResult<String>> {
+    let receiver_store = ReadonlyPrefixedStorage::new(RECEIVER_HASH_PREFIX, store);
+    receiver_store.get(account.as_str().as_bytes()).map(|data| {
+        String::from_utf8(data)
+            .map_err(|_err| StdError::invalid_utf8("stored code hash was not a valid String"))
+    })
+}
+
+pub fn set_receiver_hash<S: Storage>(store: &mut S, account: &HumanAddr, code_hash: String) {
+    let mut receiver_store = PrefixedStorage::new(RECEIVER_HASH_PREFIX, store);
+    receiver_store.set(account.as_str().as_bytes(), code_hash.as_bytes());
+}
+
 // Helpers
 
 /// Converts 16 bytes value into u128
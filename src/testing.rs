@@ -0,0 +1,252 @@
+// Mocks of the contracts a market talks to, so the market can be exercised off-chain
+use cosmwasm_std::testing::{mock_env, MockApi, MockQuerier, MockStorage, MOCK_CONTRACT_ADDR};
+
+use cosmwasm_std::{
+    coins, from_slice, to_binary, Api, Binary, CanonicalAddr, Coin, Empty, Env, Extern, HandleResponse,
+    HumanAddr, Querier, QuerierResult, QueryRequest, StdResult, SystemError, Uint128, WasmQuery,
+};
+
+use lending_common::exponential::EXP_SCALE;
+pub use lending_testing::{assert_generic_err, assert_unauthorized};
+
+use crate::collateral::get_account_borrow;
+use crate::contract::{handle, init};
+use crate::controller::{AllowedResponse, ControllerQueryMsg};
+use crate::interest_model::{InterestModelQueryMsg, RateResponse};
+use crate::msg::{Contract, HandleMsg, InitMsg};
+use crate::state::get_balance;
+
+/// address the mock controller contract is reachable at
+pub const MOCK_CONTROLLER_ADDR: &str = "controller";
+/// address the mock interest model contract is reachable at
+pub const MOCK_INTEREST_MODEL_ADDR: &str = "interest_model";
+/// native denom lent by the market of `mock_init_msg`
+pub const MOCK_DENOM: &str = "uscrt";
+
+/// Querier that answers the market's queries like its controller and interest model contracts
+/// would. Every other query is forwarded to the standard `MockQuerier`.
+pub struct MockMarketQuerier {
+    base: MockQuerier,
+    controller: HumanAddr,
+    denied: Vec<String>,
+    interest_model: HumanAddr,
+    borrow_rate: Uint128,
+    supply_rate: Uint128,
+}
+
+impl MockMarketQuerier {
+    pub fn new(balances: &[(&HumanAddr, &[Coin])]) -> Self {
+        MockMarketQuerier {
+            base: MockQuerier::new(balances),
+            controller: HumanAddr::from(MOCK_CONTROLLER_ADDR),
+            denied: vec![],
+            interest_model: HumanAddr::from(MOCK_INTEREST_MODEL_ADDR),
+            borrow_rate: Uint128::from(0u128),
+            supply_rate: Uint128::from(0u128),
+        }
+    }
+
+    /// Makes the controller reject every `action` ("mint", "redeem", "borrow", "repay",
+    /// "transfer", "liquidation" or "seizure")
+    pub fn deny(&mut self, action: &str) {
+        self.denied.push(action.to_string());
+    }
+
+    /// Makes the controller allow `action` again
+    pub fn allow(&mut self, action: &str) {
+        self.denied.retain(|denied| denied != action);
+    }
+
+    /// Sets the rates per period, as `Exp` mantissas, the interest model contract answers with
+    pub fn set_rates(&mut self, borrow_rate: Uint128, supply_rate: Uint128) {
+        self.borrow_rate = borrow_rate;
+        self.supply_rate = supply_rate;
+    }
+
+    fn handle_controller_query(&self, msg: ControllerQueryMsg) -> QuerierResult {
+        let action = match msg {
+            ControllerQueryMsg::MintAllowed { .. } => "mint",
+            ControllerQueryMsg::RedeemAllowed { .. } => "redeem",
+            ControllerQueryMsg::BorrowAllowed { .. } => "borrow",
+            ControllerQueryMsg::RepayAllowed { .. } => "repay",
+            ControllerQueryMsg::TransferAllowed { .. } => "transfer",
+            ControllerQueryMsg::LiquidateBorrowAllowed { .. } => "liquidation",
+            ControllerQueryMsg::SeizeAllowed { .. } => "seizure",
+        };
+        let allowed = !self.denied.iter().any(|denied| denied == action);
+        let reason = if allowed { None } else { Some(format!("{} is paused", action)) };
+        Ok(to_binary(&AllowedResponse { allowed, reason }))
+    }
+
+    fn handle_interest_model_query(&self, msg: InterestModelQueryMsg) -> QuerierResult {
+        let rate = match msg {
+            InterestModelQueryMsg::BorrowRate { .. } => self.borrow_rate,
+            InterestModelQueryMsg::SupplyRate { .. } => self.supply_rate,
+        };
+        Ok(to_binary(&RateResponse { rate }))
+    }
+}
+
+impl Querier for MockMarketQuerier {
+    fn raw_query(&self, bin_request: &[u8]) -> QuerierResult {
+        let request: QueryRequest<Empty> = match from_slice(bin_request) {
+            Ok(v) => v,
+            Err(e) => {
+                return Err(SystemError::InvalidRequest {
+                    error: format!("Parsing query request: {}", e),
+                    request: bin_request.into(),
+                })
+            }
+        };
+        match &request {
+            QueryRequest::Wasm(WasmQuery::Smart { contract_addr, msg, .. })
+                if *contract_addr == self.controller =>
+            {
+                match from_slice(msg.as_slice()) {
+                    Ok(controller_msg) => self.handle_controller_query(controller_msg),
+                    Err(e) => Err(invalid_contract_query(e.to_string(), msg)),
+                }
+            }
+            QueryRequest::Wasm(WasmQuery::Smart { contract_addr, msg, .. })
+                if *contract_addr == self.interest_model =>
+            {
+                match from_slice(msg.as_slice()) {
+                    Ok(interest_model_msg) => self.handle_interest_model_query(interest_model_msg),
+                    Err(e) => Err(invalid_contract_query(e.to_string(), msg)),
+                }
+            }
+            _ => self.base.raw_query(bin_request),
+        }
+    }
+}
+
+fn invalid_contract_query(error: String, msg: &Binary) -> SystemError {
+    SystemError::InvalidRequest {
+        error: format!("Parsing contract query: {}", error),
+        request: msg.clone(),
+    }
+}
+
+/// Returns mock dependencies whose querier acts as the controller at `MOCK_CONTROLLER_ADDR`
+/// and the interest model at `MOCK_INTEREST_MODEL_ADDR`
+pub fn mock_market_dependencies(
+    canonical_length: usize,
+    contract_balance: &[Coin],
+) -> Extern<MockStorage, MockApi, MockMarketQuerier> {
+    let contract_addr = HumanAddr::from(MOCK_CONTRACT_ADDR);
+    Extern {
+        storage: MockStorage::default(),
+        api: MockApi::new(canonical_length),
+        querier: MockMarketQuerier::new(&[(&contract_addr, contract_balance)]),
+    }
+}
+
+/// Returns the mock controller as a contract a market can be configured with
+pub fn mock_controller() -> Contract {
+    Contract {
+        address: HumanAddr::from(MOCK_CONTROLLER_ADDR),
+        code_hash: "controller_code_hash".to_string(),
+    }
+}
+
+/// Returns the mock interest model as a contract a market can be configured with
+pub fn mock_interest_model() -> Contract {
+    Contract {
+        address: HumanAddr::from(MOCK_INTEREST_MODEL_ADDR),
+        code_hash: "interest_model_code_hash".to_string(),
+    }
+}
+
+/// Returns the InitMsg of a `MOCK_DENOM` market without a controller, with an exchange rate of 1,
+/// a collateral factor of 0.5 and the default interest model
+pub fn mock_init_msg() -> InitMsg {
+    InitMsg {
+        name: "Compound Secret".to_string(),
+        total_supply: Uint128::from(0u128),
+        decimals: 6,
+        symbol: "CSCRT".to_string(),
+        initial_exchange_rate: Uint128::from(EXP_SCALE),
+        reserve_factor: Uint128::from(EXP_SCALE / 10),
+        borrow_index: Uint128::from(EXP_SCALE),
+        max_borrow_rate: Uint128::from(5_000_000_000_000u128),
+        denom: MOCK_DENOM.to_string(),
+        close_factor: Uint128::from(EXP_SCALE / 2),
+        liquidation_incentive: Uint128::from(EXP_SCALE + EXP_SCALE / 10),
+        collateral_factor: Uint128::from(EXP_SCALE / 2),
+        controller: None,
+        underlying_token: None,
+        admin: None,
+        interest_model: None,
+        compound_interest: None,
+        accrual_mode: None,
+        prng_seed: Binary::from(b"seed".to_vec()),
+        quiet_mode: None,
+    }
+}
+
+/// dependencies of a market instantiated with the mocks above
+pub type MockDeps = Extern<MockStorage, MockApi, MockMarketQuerier>;
+
+/// Instantiates a market from `msg`, with "admin" as its admin
+pub fn init_market(msg: InitMsg) -> StdResult<MockDeps> {
+    let mut deps = mock_market_dependencies(20, &[]);
+    init(&mut deps, mock_env("admin", &[]), msg)?;
+    Ok(deps)
+}
+
+/// Returns the market of `mock_init_msg`, checked by `controller` if one is given
+pub fn mock_market(controller: Option<Contract>) -> MockDeps {
+    let mut msg = mock_init_msg();
+    msg.controller = controller;
+    init_market(msg).unwrap()
+}
+
+/// Handles `msg` from `sender`, who attaches `sent` of `MOCK_DENOM` unless it is zero
+pub fn run(deps: &mut MockDeps, sender: &str, sent: u128, msg: HandleMsg) -> StdResult<HandleResponse> {
+    let sent_funds = if sent == 0 { vec![] } else { coins(sent, MOCK_DENOM) };
+    handle(deps, mock_env(sender, &sent_funds), msg)
+}
+
+/// Handles `msg` in `env`, with `sent` of `MOCK_DENOM` attached unless it is zero
+pub fn run_at(deps: &mut MockDeps, mut env: Env, sent: u128, msg: HandleMsg) -> StdResult<HandleResponse> {
+    env.message.sent_funds = if sent == 0 { vec![] } else { coins(sent, MOCK_DENOM) };
+    handle(deps, env, msg)
+}
+
+pub fn mint(deps: &mut MockDeps, sender: &str, amount: u128) -> StdResult<HandleResponse> {
+    run(deps, sender, amount, HandleMsg::Mint {})
+}
+
+pub fn redeem(deps: &mut MockDeps, sender: &str, tokens: u128) -> StdResult<HandleResponse> {
+    run(deps, sender, 0, HandleMsg::Redeem { tokens: Uint128::from(tokens) })
+}
+
+pub fn borrow(deps: &mut MockDeps, sender: &str, amount: u128) -> StdResult<HandleResponse> {
+    run(deps, sender, 0, HandleMsg::Borrow { borrow_amount: Uint128::from(amount) })
+}
+
+pub fn repay(deps: &mut MockDeps, sender: &str, amount: u128) -> StdResult<HandleResponse> {
+    run(deps, sender, amount, HandleMsg::RepayBorrow {})
+}
+
+pub fn transfer(deps: &mut MockDeps, sender: &str, recipient: &str, amount: u128) -> StdResult<HandleResponse> {
+    let msg = HandleMsg::Transfer {
+        recipient: HumanAddr::from(recipient),
+        amount: Uint128::from(amount),
+    };
+    run(deps, sender, 0, msg)
+}
+
+pub fn canonical(deps: &MockDeps, address: &str) -> CanonicalAddr {
+    deps.api.canonical_address(&HumanAddr::from(address)).unwrap()
+}
+
+/// Returns the cToken balance of `address`
+pub fn balance(deps: &MockDeps, address: &str) -> u128 {
+    get_balance(&deps.storage, &canonical(deps, address)).unwrap()
+}
+
+/// Returns the borrow balance of `address` at the current borrow index
+pub fn account_borrow(deps: &MockDeps, address: &str) -> u128 {
+    get_account_borrow(deps, &canonical(deps, address)).unwrap()
+}
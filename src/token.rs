@@ -1,13 +1,21 @@
 use cosmwasm_std::{
-    log, Api, CanonicalAddr, Env, Extern, HandleResponse, HumanAddr, Querier,
-    StdError, StdResult, Storage, Uint128,
+    log, to_binary, Api, Binary, BlockInfo, CanonicalAddr, CosmosMsg, Env, Extern, HandleResponse, HumanAddr,
+    Querier, StdError, StdResult, Storage, Uint128,
 };
 //use cosmwasm_storage::{PrefixedStorage, ReadonlyPrefixedStorage};
 
 //use std::convert::TryInto;
 
+use lending_common::exponential::{add_uint, sub_uint};
+
+use crate::collateral::accrue_interest;
+use crate::controller::{redeem_allowed, transfer_allowed};
+use crate::history::{store_burn, store_transfer};
+use crate::msg::{HandleAnswer, ResponseStatus};
+use crate::receiver::Snip20ReceiveMsg;
 use crate::state::{
-    get_allowance, get_balance, set_allowance, set_balance
+    get_allowance, get_balance, get_config, get_receiver_hash, set_allowance, set_balance,
+    set_config, set_receiver_hash, Allowance,
 };
 
 pub fn try_transfer<S: Storage, A: Api, Q: Querier>(
@@ -16,25 +24,28 @@ pub fn try_transfer<S: Storage, A: Api, Q: Querier>(
     recipient: &HumanAddr,
     amount: &Uint128,
 ) -> StdResult<HandleResponse> {
-    let sender_address_raw = deps.api.canonical_address(recipient)?;
+    let sender_address_raw = deps.api.canonical_address(&env.message.sender)?;
     let recipient_address_raw = deps.api.canonical_address(recipient)?;
     let amount_raw = amount.u128();
 
+    // Check if the sender keeps enough collateral for its borrow after the transfer
+    transfer_allowed(deps, env.clone(), &env.message.sender, recipient, amount_raw)?;
+
     perform_transfer(
         &mut deps.storage,
         &sender_address_raw,
         &recipient_address_raw,
+        &sender_address_raw,
         amount_raw,
+        &env.block,
     )?;
 
     let res = HandleResponse {
         messages: vec![],
-        log: vec![
-            log("action", "transfer"),
-            log("sender", env.message.sender.as_str()),
-            log("recipient", recipient.as_str()),
-        ],
-        data: None,
+        log: vec![log("action", "transfer")],
+        data: Some(to_binary(&HandleAnswer::Transfer {
+            status: ResponseStatus::Success,
+        })?),
     };
     Ok(res)
 }
@@ -51,40 +62,240 @@ pub fn try_transfer_from<S: Storage, A: Api, Q: Querier>(
     let recipient_address_raw = deps.api.canonical_address(recipient)?;
     let amount_raw = amount.u128();
 
-    let mut allowance = get_allowance(&deps.storage, &owner_address_raw, &spender_address_raw)?;
-    if allowance < amount_raw {
-        return Err(StdError::generic_err(format!(
-            "Insufficient allowance: allowance={}, required={}",
-            allowance, amount_raw
-        )));
-    }
-    allowance -= amount_raw;
-    set_allowance(
-        &mut deps.storage,
-        &owner_address_raw,
-        &spender_address_raw,
-        allowance,
-    )?;
+    // Check if the owner keeps enough collateral for its borrow after the transfer
+    transfer_allowed(deps, env.clone(), owner, recipient, amount_raw)?;
+
+    use_allowance(&mut deps.storage, &env, &owner_address_raw, &spender_address_raw, amount_raw)?;
     perform_transfer(
         &mut deps.storage,
         &owner_address_raw,
         &recipient_address_raw,
+        &spender_address_raw,
         amount_raw,
+        &env.block,
     )?;
 
     let res = HandleResponse {
         messages: vec![],
-        log: vec![
-            log("action", "transfer_from"),
-            log("spender", env.message.sender.as_str()),
-            log("sender", owner.as_str()),
-            log("recipient", recipient.as_str()),
-        ],
-        data: None,
+        log: vec![log("action", "transfer_from")],
+        data: Some(to_binary(&HandleAnswer::TransferFrom {
+            status: ResponseStatus::Success,
+        })?),
     };
     Ok(res)
 }
 
+pub fn try_send<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    recipient: HumanAddr,
+    recipient_code_hash: Option<String>,
+    amount: Uint128,
+    msg: Option<Binary>,
+) -> StdResult<HandleResponse> {
+    let sender = env.message.sender.clone();
+    let sender_address_raw = deps.api.canonical_address(&sender)?;
+    let recipient_address_raw = deps.api.canonical_address(&recipient)?;
+
+    // Check if the sender keeps enough collateral for its borrow after the transfer
+    transfer_allowed(deps, env.clone(), &sender, &recipient, amount.u128())?;
+
+    perform_transfer(
+        &mut deps.storage,
+        &sender_address_raw,
+        &recipient_address_raw,
+        &sender_address_raw,
+        amount.u128(),
+        &env.block,
+    )?;
+
+    let messages = receiver_callback(
+        &deps.storage,
+        sender.clone(),
+        sender,
+        recipient,
+        recipient_code_hash,
+        amount,
+        msg,
+    )?;
+
+    Ok(HandleResponse {
+        messages,
+        log: vec![log("action", "send")],
+        data: Some(to_binary(&HandleAnswer::Send {
+            status: ResponseStatus::Success,
+        })?),
+    })
+}
+
+pub fn try_send_from<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    owner: HumanAddr,
+    recipient: HumanAddr,
+    recipient_code_hash: Option<String>,
+    amount: Uint128,
+    msg: Option<Binary>,
+) -> StdResult<HandleResponse> {
+    let spender_address_raw = deps.api.canonical_address(&env.message.sender)?;
+    let owner_address_raw = deps.api.canonical_address(&owner)?;
+    let recipient_address_raw = deps.api.canonical_address(&recipient)?;
+
+    // Check if the owner keeps enough collateral for its borrow after the transfer
+    transfer_allowed(deps, env.clone(), &owner, &recipient, amount.u128())?;
+
+    use_allowance(&mut deps.storage, &env, &owner_address_raw, &spender_address_raw, amount.u128())?;
+    perform_transfer(
+        &mut deps.storage,
+        &owner_address_raw,
+        &recipient_address_raw,
+        &spender_address_raw,
+        amount.u128(),
+        &env.block,
+    )?;
+
+    let messages = receiver_callback(
+        &deps.storage,
+        env.message.sender,
+        owner,
+        recipient,
+        recipient_code_hash,
+        amount,
+        msg,
+    )?;
+
+    Ok(HandleResponse {
+        messages,
+        log: vec![log("action", "send_from")],
+        data: Some(to_binary(&HandleAnswer::SendFrom {
+            status: ResponseStatus::Success,
+        })?),
+    })
+}
+
+pub fn try_register_receive<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    code_hash: String,
+) -> StdResult<HandleResponse> {
+    set_receiver_hash(&mut deps.storage, &env.message.sender, code_hash);
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![log("action", "register_receive")],
+        data: Some(to_binary(&HandleAnswer::RegisterReceive {
+            status: ResponseStatus::Success,
+        })?),
+    })
+}
+
+pub fn try_burn<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    amount: Uint128,
+) -> StdResult<HandleResponse> {
+    let owner = env.message.sender.clone();
+    perform_burn(deps, env, &owner, amount.u128())?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![log("action", "burn")],
+        data: Some(to_binary(&HandleAnswer::Burn {
+            status: ResponseStatus::Success,
+        })?),
+    })
+}
+
+pub fn try_burn_from<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    owner: HumanAddr,
+    amount: Uint128,
+) -> StdResult<HandleResponse> {
+    let spender_address_raw = deps.api.canonical_address(&env.message.sender)?;
+    let owner_address_raw = deps.api.canonical_address(&owner)?;
+
+    use_allowance(&mut deps.storage, &env, &owner_address_raw, &spender_address_raw, amount.u128())?;
+    perform_burn(deps, env, &owner, amount.u128())?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![log("action", "burn_from")],
+        data: Some(to_binary(&HandleAnswer::BurnFrom {
+            status: ResponseStatus::Success,
+        })?),
+    })
+}
+
+pub fn try_increase_allowance<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    spender: HumanAddr,
+    amount: Uint128,
+    expiration: Option<u64>,
+) -> StdResult<HandleResponse> {
+    let owner_address_raw = deps.api.canonical_address(&env.message.sender)?;
+    let spender_address_raw = deps.api.canonical_address(&spender)?;
+
+    let mut allowance = get_allowance(&deps.storage, &owner_address_raw, &spender_address_raw)?;
+
+    // An expired allowance is worth nothing, so it restarts from zero without an expiration
+    if allowance.is_expired_at(&env.block) {
+        allowance.amount = amount.u128();
+        allowance.expiration = None;
+    } else {
+        allowance.amount = allowance.amount.saturating_add(amount.u128());
+    }
+    if expiration.is_some() {
+        allowance.expiration = expiration;
+    }
+    set_allowance(&mut deps.storage, &owner_address_raw, &spender_address_raw, &allowance)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![log("action", "increase_allowance")],
+        data: Some(to_binary(&HandleAnswer::IncreaseAllowance {
+            owner: env.message.sender,
+            spender,
+            allowance: Uint128::from(allowance.amount),
+        })?),
+    })
+}
+
+pub fn try_decrease_allowance<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    spender: HumanAddr,
+    amount: Uint128,
+    expiration: Option<u64>,
+) -> StdResult<HandleResponse> {
+    let owner_address_raw = deps.api.canonical_address(&env.message.sender)?;
+    let spender_address_raw = deps.api.canonical_address(&spender)?;
+
+    let mut allowance = get_allowance(&deps.storage, &owner_address_raw, &spender_address_raw)?;
+
+    if allowance.is_expired_at(&env.block) {
+        allowance.amount = 0;
+        allowance.expiration = None;
+    } else {
+        allowance.amount = allowance.amount.saturating_sub(amount.u128());
+    }
+    if expiration.is_some() {
+        allowance.expiration = expiration;
+    }
+    set_allowance(&mut deps.storage, &owner_address_raw, &spender_address_raw, &allowance)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![log("action", "decrease_allowance")],
+        data: Some(to_binary(&HandleAnswer::DecreaseAllowance {
+            owner: env.message.sender,
+            spender,
+            allowance: Uint128::from(allowance.amount),
+        })?),
+    })
+}
+
 pub fn try_approve<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
@@ -97,25 +308,103 @@ pub fn try_approve<S: Storage, A: Api, Q: Querier>(
         &mut deps.storage,
         &owner_address_raw,
         &spender_address_raw,
-        amount.u128(),
+        &Allowance {
+            amount: amount.u128(),
+            expiration: None,
+        },
     )?;
     let res = HandleResponse {
         messages: vec![],
-        log: vec![
-            log("action", "approve"),
-            log("owner", env.message.sender.as_str()),
-            log("spender", spender.as_str()),
-        ],
-        data: None,
+        log: vec![log("action", "approve")],
+        data: Some(to_binary(&HandleAnswer::Approve {
+            status: ResponseStatus::Success,
+        })?),
     };
     Ok(res)
 }
 
+/// Spends `amount` of the allowance `owner` gave `spender`
+fn use_allowance<S: Storage>(
+    store: &mut S,
+    env: &Env,
+    owner: &CanonicalAddr,
+    spender: &CanonicalAddr,
+    amount: u128,
+) -> StdResult<()> {
+    let mut allowance = get_allowance(store, owner, spender)?;
+
+    if allowance.is_expired_at(&env.block) {
+        return Err(StdError::generic_err(format!(
+            "Insufficient allowance: allowance expired at {}",
+            allowance.expiration.unwrap_or_default())
+        )
+        );
+    }
+    if allowance.amount < amount {
+        return Err(StdError::generic_err(format!(
+            "Insufficient allowance: allowance={}, required={}",
+            allowance.amount, amount
+        )));
+    }
+    allowance.amount = sub_uint(allowance.amount, amount)?;
+    set_allowance(store, owner, spender, &allowance)
+}
+
+/// Returns the Receive callback of a send to `recipient`, if it registered a code hash or one was given
+fn receiver_callback<S: Storage>(
+    store: &S,
+    sender: HumanAddr,
+    from: HumanAddr,
+    recipient: HumanAddr,
+    recipient_code_hash: Option<String>,
+    amount: Uint128,
+    msg: Option<Binary>,
+) -> StdResult<Vec<CosmosMsg>> {
+    let code_hash = match recipient_code_hash {
+        Some(code_hash) => Some(code_hash),
+        None => get_receiver_hash(store, &recipient).transpose()?,
+    };
+
+    match code_hash {
+        Some(code_hash) => {
+            let receive_msg = Snip20ReceiveMsg::new(sender, from, amount, msg);
+            Ok(vec![receive_msg.into_cosmos_msg(code_hash, recipient)?])
+        },
+        None => Ok(vec![]),
+    }
+}
+
+/// Burns `amount` cTokens of `owner` without paying out their underlying
+fn perform_burn<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    owner: &HumanAddr,
+    amount: u128,
+) -> StdResult<()> {
+    // The exchange rate changes with the supply, so interest is settled at the old supply first
+    accrue_interest(deps, env.clone())?;
+
+    // Burnt cTokens no longer count as collateral
+    redeem_allowed(deps, env.clone(), owner, amount)?;
+
+    let owner_address_raw = deps.api.canonical_address(owner)?;
+    let burner_address_raw = deps.api.canonical_address(&env.message.sender)?;
+    burn_tokens(&mut deps.storage, &owner_address_raw, amount)?;
+    store_burn(&mut deps.storage, &owner_address_raw, &burner_address_raw, amount, &env.block)?;
+
+    let mut config = get_config(&deps.storage)?;
+    config.total_supply = sub_uint(config.total_supply, amount)?;
+    set_config(&mut deps.storage, &config)
+}
+
+/// Moves `amount` cTokens from `from` to `to` on behalf of `sender` and records the transfer
 fn perform_transfer<T: Storage>(
     store: &mut T,
     from: &CanonicalAddr,
     to: &CanonicalAddr,
+    sender: &CanonicalAddr,
     amount: u128,
+    block: &BlockInfo,
 ) -> StdResult<()> {
     let mut from_balance = get_balance(store, from)?;
     if from_balance < amount {
@@ -126,12 +415,14 @@ fn perform_transfer<T: Storage>(
             amount
         )));
     }
-    from_balance -= amount;
+    from_balance = sub_uint(from_balance, amount)?;
     set_balance(store, from, from_balance)?;
 
     let mut to_balance = get_balance(store, to)?;
-    to_balance += amount;
-    set_balance(store, to, to_balance)
+    to_balance = add_uint(to_balance, amount)?;
+    set_balance(store, to, to_balance)?;
+
+    store_transfer(store, from, sender, to, amount, block)
 }
 
 pub fn mint_tokens<T: Storage>(
@@ -140,7 +431,7 @@ pub fn mint_tokens<T: Storage>(
     amount: u128,
 ) -> StdResult<()> {
     let mut to_balance = get_balance(store, to)?;
-    to_balance += amount;
+    to_balance = add_uint(to_balance, amount)?;
     set_balance(store, to, to_balance)
 }
 
@@ -151,6 +442,208 @@ pub fn burn_tokens<T: Storage>(
     amount: u128,
 ) -> StdResult<()> {
     let mut to_balance = get_balance(store, to)?;
-    to_balance -= amount;
+    to_balance = sub_uint(to_balance, amount)?;
     set_balance(store, to, to_balance)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmwasm_std::testing::mock_env;
+    use cosmwasm_std::{from_binary, WasmMsg};
+
+    use crate::contract::handle;
+    use crate::msg::HandleMsg;
+    use crate::testing::{
+        assert_generic_err, balance, borrow, canonical, mint, mock_market, run, MockDeps,
+    };
+
+    fn init_market() -> MockDeps {
+        let mut deps = mock_market(None);
+        mint(&mut deps, "alice", 1_000).unwrap();
+        deps
+    }
+
+    fn env_at(sender: &str, time: u64) -> Env {
+        let mut env = mock_env(sender, &[]);
+        env.block.time = time;
+        env
+    }
+
+    fn increase_allowance(
+        deps: &mut MockDeps,
+        time: u64,
+        amount: u128,
+        expiration: Option<u64>,
+    ) -> StdResult<HandleResponse> {
+        let msg = HandleMsg::IncreaseAllowance {
+            spender: HumanAddr::from("bob"),
+            amount: Uint128::from(amount),
+            expiration,
+            padding: None,
+        };
+        handle(deps, env_at("alice", time), msg)
+    }
+
+    fn transfer_from(deps: &mut MockDeps, time: u64, amount: u128) -> StdResult<HandleResponse> {
+        let msg = HandleMsg::TransferFrom {
+            owner: HumanAddr::from("alice"),
+            recipient: HumanAddr::from("bob"),
+            amount: Uint128::from(amount),
+        };
+        handle(deps, env_at("bob", time), msg)
+    }
+
+    fn send(deps: &mut MockDeps, recipient: &str, code_hash: Option<&str>) -> Vec<CosmosMsg> {
+        let msg = HandleMsg::Send {
+            recipient: HumanAddr::from(recipient),
+            recipient_code_hash: code_hash.map(String::from),
+            amount: Uint128::from(10u128),
+            msg: None,
+            padding: None,
+        };
+        run(deps, "alice", 0, msg).unwrap().messages
+    }
+
+    fn allowance(deps: &MockDeps) -> Allowance {
+        get_allowance(&deps.storage, &canonical(deps, "alice"), &canonical(deps, "bob")).unwrap()
+    }
+
+    #[test]
+    fn increasing_an_expired_allowance_restarts_from_zero() {
+        let mut deps = init_market();
+
+        increase_allowance(&mut deps, 100, 100, Some(1_000)).unwrap();
+        increase_allowance(&mut deps, 999, 50, None).unwrap();
+        assert_eq!(allowance(&deps), Allowance { amount: 150, expiration: Some(1_000) });
+
+        // Past its expiration the allowance is worth nothing, so nothing of it is kept
+        increase_allowance(&mut deps, 1_000, 30, Some(2_000)).unwrap();
+        assert_eq!(allowance(&deps), Allowance { amount: 30, expiration: Some(2_000) });
+    }
+
+    #[test]
+    fn an_expired_allowance_restarts_without_an_expiration() {
+        let mut deps = init_market();
+        increase_allowance(&mut deps, 100, 100, Some(1_000)).unwrap();
+
+        // The old expiration does not carry over, so the new allowance is usable right away
+        increase_allowance(&mut deps, 1_000, 30, None).unwrap();
+        assert_eq!(allowance(&deps), Allowance { amount: 30, expiration: None });
+        transfer_from(&mut deps, 1_001, 30).unwrap();
+        assert_eq!(balance(&deps, "bob"), 30);
+
+        increase_allowance(&mut deps, 1_001, 100, Some(2_000)).unwrap();
+        let msg = HandleMsg::DecreaseAllowance {
+            spender: HumanAddr::from("bob"),
+            amount: Uint128::from(10u128),
+            expiration: None,
+            padding: None,
+        };
+        handle(&mut deps, env_at("alice", 2_000), msg).unwrap();
+        assert_eq!(allowance(&deps), Allowance { amount: 0, expiration: None });
+    }
+
+    #[test]
+    fn an_expired_allowance_cannot_be_used() {
+        let mut deps = init_market();
+        increase_allowance(&mut deps, 100, 100, Some(1_000)).unwrap();
+
+        transfer_from(&mut deps, 999, 10).unwrap();
+        assert_eq!(allowance(&deps).amount, 90);
+        assert_generic_err(
+            transfer_from(&mut deps, 999, 91),
+            "Insufficient allowance: allowance=90, required=91",
+        );
+
+        assert_generic_err(
+            transfer_from(&mut deps, 1_000, 10),
+            "Insufficient allowance: allowance expired at 1000",
+        );
+        let msg = HandleMsg::BurnFrom {
+            owner: HumanAddr::from("alice"),
+            amount: Uint128::from(10u128),
+            padding: None,
+        };
+        assert_generic_err(handle(&mut deps, env_at("bob", 1_000), msg), "allowance expired at 1000");
+        assert_eq!(balance(&deps, "alice"), 990);
+        assert_eq!(allowance(&deps).amount, 90);
+    }
+
+    #[test]
+    fn burn_keeps_the_borrow_collateralized() {
+        let mut deps = init_market();
+        borrow(&mut deps, "alice", 400).unwrap();
+
+        // 202 cTokens are worth 101 of collateral, one more than the liquidity of 100
+        let burn = |amount: u128| HandleMsg::Burn { amount: Uint128::from(amount), padding: None };
+        assert_generic_err(
+            run(&mut deps, "alice", 0, burn(202)),
+            "Insufficient collateral to redeem",
+        );
+        assert_eq!(balance(&deps, "alice"), 1_000);
+
+        run(&mut deps, "alice", 0, burn(200)).unwrap();
+        assert_eq!(balance(&deps, "alice"), 800);
+        assert_eq!(get_config(&deps.storage).unwrap().total_supply, 800);
+    }
+
+    #[test]
+    fn spending_an_allowance_answers_with_its_own_variant() {
+        let mut deps = init_market();
+        let time = mock_env("alice", &[]).block.time;
+        increase_allowance(&mut deps, time, 100, None).unwrap();
+
+        let send_from = HandleMsg::SendFrom {
+            owner: HumanAddr::from("alice"),
+            recipient: HumanAddr::from("carol"),
+            recipient_code_hash: None,
+            amount: Uint128::from(10u128),
+            msg: None,
+            padding: None,
+        };
+        let response = run(&mut deps, "bob", 0, send_from).unwrap();
+        match from_binary(&response.data.unwrap()).unwrap() {
+            HandleAnswer::SendFrom { status: ResponseStatus::Success } => {},
+            other => panic!("unexpected answer: {:?}", other),
+        }
+
+        let burn_from = HandleMsg::BurnFrom { owner: HumanAddr::from("alice"), amount: Uint128::from(10u128), padding: None };
+        let response = run(&mut deps, "bob", 0, burn_from).unwrap();
+        match from_binary(&response.data.unwrap()).unwrap() {
+            HandleAnswer::BurnFrom { status: ResponseStatus::Success } => {},
+            other => panic!("unexpected answer: {:?}", other),
+        }
+        assert_eq!(balance(&deps, "alice"), 980);
+        assert_eq!(allowance(&deps).amount, 80);
+    }
+
+    #[test]
+    fn receive_is_called_only_with_a_known_code_hash() {
+        let mut deps = init_market();
+
+        assert!(send(&mut deps, "wallet", None).is_empty());
+
+        let messages = send(&mut deps, "wallet", Some("given_hash"));
+        match messages.as_slice() {
+            [CosmosMsg::Wasm(WasmMsg::Execute { contract_addr, callback_code_hash, .. })] => {
+                assert_eq!(contract_addr, &HumanAddr::from("wallet"));
+                assert_eq!(callback_code_hash, "given_hash");
+            },
+            other => panic!("unexpected messages: {:?}", other),
+        }
+
+        let msg = HandleMsg::RegisterReceive { code_hash: "registered_hash".to_string(), padding: None };
+        run(&mut deps, "receiver", 0, msg).unwrap();
+        let messages = send(&mut deps, "receiver", None);
+        match messages.as_slice() {
+            [CosmosMsg::Wasm(WasmMsg::Execute { contract_addr, callback_code_hash, .. })] => {
+                assert_eq!(contract_addr, &HumanAddr::from("receiver"));
+                assert_eq!(callback_code_hash, "registered_hash");
+            },
+            other => panic!("unexpected messages: {:?}", other),
+        }
+        assert_eq!(balance(&deps, "wallet"), 20);
+        assert_eq!(balance(&deps, "receiver"), 10);
+    }
+}
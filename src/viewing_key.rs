@@ -0,0 +1,67 @@
+use std::fmt;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use cosmwasm_std::{Binary, Env};
+
+use secret_toolkit::crypto::{sha_256, Prng};
+
+pub const VIEWING_KEY_SIZE: usize = 32;
+pub const VIEWING_KEY_PREFIX: &str = "api_key_";
+
+/// key an account uses to authenticate queries about its balances and positions
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+pub struct ViewingKey(pub String);
+
+impl ViewingKey {
+    /// Returns true if the key hashes to `hashed_pw`, compared in constant time
+    pub fn check_viewing_key(&self, hashed_pw: &[u8]) -> bool {
+        let mine_hashed = create_hashed_password(&self.0);
+        ct_slice_compare(&mine_hashed, hashed_pw)
+    }
+
+    /// Returns a new random key derived from the contract seed, the block and the user entropy
+    pub fn new(env: &Env, seed: &[u8], entropy: &[u8]) -> Self {
+        // 16 here represents the lengths in bytes of the block height and time.
+        let entropy_len = 16 + env.message.sender.len() + entropy.len();
+        let mut rng_entropy = Vec::with_capacity(entropy_len);
+        rng_entropy.extend_from_slice(&env.block.height.to_be_bytes());
+        rng_entropy.extend_from_slice(&env.block.time.to_be_bytes());
+        rng_entropy.extend_from_slice(&env.message.sender.0.as_bytes());
+        rng_entropy.extend_from_slice(entropy);
+
+        let mut rng = Prng::new(seed, &rng_entropy);
+        let rand_slice = rng.rand_bytes();
+        let key = sha_256(&rand_slice);
+
+        Self(VIEWING_KEY_PREFIX.to_string() + &Binary::from(&key[..]).to_base64())
+    }
+
+    /// Returns the hash of the key, which is what gets stored
+    pub fn to_hashed(&self) -> [u8; VIEWING_KEY_SIZE] {
+        create_hashed_password(&self.0)
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        self.0.as_bytes()
+    }
+}
+
+impl fmt::Display for ViewingKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+fn create_hashed_password(s1: &str) -> [u8; VIEWING_KEY_SIZE] {
+    sha_256(s1.as_bytes())
+}
+
+/// Compares two slices without short-circuiting, so the time taken does not leak the key
+fn ct_slice_compare(s1: &[u8], s2: &[u8]) -> bool {
+    if s1.len() != s2.len() {
+        return false;
+    }
+    s1.iter().zip(s2.iter()).fold(0u8, |acc, (a, b)| acc | (a ^ b)) == 0
+}